@@ -0,0 +1,211 @@
+use std::hash::Hash;
+
+// A set of small non-negative integers stored as bits. Implemented for fixed-width unsigned
+// integers, which panic when inserting an index that doesn't fit, and for `DynBitset`, which grows
+// as needed.
+pub trait Bitset: Clone + Eq + Hash + Default {
+    fn insert(&mut self, index: usize);
+    fn len(&self) -> usize;
+    fn union(&self, other: &Self) -> Self;
+    fn intersection(&self, other: &Self) -> Self;
+    fn is_disjoint(&self, other: &Self) -> bool;
+
+    fn singleton(index: usize) -> Self {
+        let mut set = Self::default();
+        set.insert(index);
+        set
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_;
+
+    // Converts a set of elements below N to and from a number in 0..2^N, so that it can be used to
+    // index dense tables. Only meaningful for small N.
+    fn to_dense_index(&self) -> usize;
+    fn from_dense_index(index: usize) -> Self;
+}
+
+macro_rules! impl_fixed_width_bitset {
+    ($($int:ty),*) => {$(
+        impl Bitset for $int {
+            fn insert(&mut self, index: usize) {
+                assert!(index < <$int>::BITS as usize, "bit index {index} out of range");
+                *self |= 1 << index;
+            }
+
+            fn len(&self) -> usize {
+                self.count_ones() as usize
+            }
+
+            fn union(&self, other: &Self) -> Self {
+                self | other
+            }
+
+            fn intersection(&self, other: &Self) -> Self {
+                self & other
+            }
+
+            fn is_disjoint(&self, other: &Self) -> bool {
+                self & other == 0
+            }
+
+            fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+                let mut remaining = *self;
+                std::iter::from_fn(move || {
+                    if remaining == 0 {
+                        return None;
+                    }
+                    let index = remaining.trailing_zeros() as usize;
+                    remaining &= remaining - 1;
+                    Some(index)
+                })
+            }
+
+            fn to_dense_index(&self) -> usize {
+                *self as usize
+            }
+
+            fn from_dense_index(index: usize) -> Self {
+                index as $int
+            }
+        }
+    )*};
+}
+
+impl_fixed_width_bitset!(u32, u64, u128);
+
+// A growable bitset. The trailing words are always non-zero, so that equal sets have equal
+// representations, and thus the derived Eq and Hash are correct.
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub struct DynBitset {
+    words: Vec<u64>,
+}
+
+impl DynBitset {
+    fn from_words(mut words: Vec<u64>) -> DynBitset {
+        while words.last() == Some(&0) {
+            words.pop();
+        }
+        DynBitset { words }
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        self.words.get(i).copied().unwrap_or(0)
+    }
+
+    fn zip_words(&self, other: &Self, f: impl Fn(u64, u64) -> u64) -> DynBitset {
+        let len = self.words.len().max(other.words.len());
+        let words = (0..len).map(|i| f(self.word(i), other.word(i))).collect();
+        DynBitset::from_words(words)
+    }
+}
+
+impl Bitset for DynBitset {
+    fn insert(&mut self, index: usize) {
+        let word_index = index / 64;
+        if word_index >= self.words.len() {
+            self.words.resize(word_index + 1, 0);
+        }
+        self.words[word_index] |= 1 << (index % 64);
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        self.zip_words(other, |a, b| a | b)
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        self.zip_words(other, |a, b| a & b)
+    }
+
+    fn is_disjoint(&self, other: &Self) -> bool {
+        self.words.iter().zip(&other.words).all(|(a, b)| a & b == 0)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, &word)| {
+                (0..64)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| word_index * 64 + bit)
+            })
+    }
+
+    fn to_dense_index(&self) -> usize {
+        assert!(self.words.len() <= 1, "set too big for a dense index");
+        self.word(0) as usize
+    }
+
+    fn from_dense_index(index: usize) -> Self {
+        DynBitset::from_words(vec![index as u64])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, vecs};
+    use std::collections::BTreeSet;
+
+    // Checks the set operations against BTreeSet. Each generated value is split into two sets:
+    // values below `max_index` go to the first one, and the rest go to the second one.
+    fn agrees_with_btree_set<B: Bitset>(max_index: usize) {
+        let max = max_index as i64;
+        check(&vecs(ints(0..=2 * max - 1), 0..=40), |values| {
+            let mut sets = (B::default(), B::default());
+            let mut expected = (BTreeSet::new(), BTreeSet::new());
+            for &value in values {
+                let value = value as usize;
+                if value < max_index {
+                    sets.0.insert(value);
+                    expected.0.insert(value);
+                } else {
+                    sets.1.insert(value - max_index);
+                    expected.1.insert(value - max_index);
+                }
+            }
+            let elements = |set: &B| set.iter().collect::<Vec<_>>();
+            let union: Vec<_> = expected.0.union(&expected.1).copied().collect();
+            let intersection: Vec<_> = expected.0.intersection(&expected.1).copied().collect();
+            elements(&sets.0) == expected.0.iter().copied().collect::<Vec<_>>()
+                && sets.0.len() == expected.0.len()
+                && elements(&sets.0.union(&sets.1)) == union
+                && sets.0.union(&sets.1).len() == union.len()
+                && elements(&sets.0.intersection(&sets.1)) == intersection
+                && sets.0.intersection(&sets.1).len() == intersection.len()
+                && sets.0.is_disjoint(&sets.1) == intersection.is_empty()
+        });
+    }
+
+    #[test]
+    fn set_operations() {
+        agrees_with_btree_set::<u32>(32);
+        agrees_with_btree_set::<u64>(64);
+        agrees_with_btree_set::<u128>(128);
+        agrees_with_btree_set::<DynBitset>(300);
+    }
+
+    #[test]
+    fn dyn_bitset_equality_ignores_capacity() {
+        let a = DynBitset::singleton(3).union(&DynBitset::singleton(200));
+        let b = DynBitset::singleton(3).union(&DynBitset::singleton(100));
+        assert_eq!(a.intersection(&b), DynBitset::singleton(3));
+        assert_eq!(a.intersection(&b).to_dense_index(), 8);
+        assert_eq!(DynBitset::from_dense_index(8), DynBitset::singleton(3));
+        assert!(DynBitset::from_dense_index(0).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "bit index 64 out of range")]
+    fn fixed_width_overflow() {
+        u64::singleton(64);
+    }
+}
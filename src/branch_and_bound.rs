@@ -0,0 +1,491 @@
+use rayon::prelude::*;
+use rustc_hash::FxHashMap as HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+// A maximization problem to be solved with branch-and-bound search.
+pub trait Problem {
+    type State;
+    // States with the same dominance key are interchangeable except for their value, so only the
+    // best one needs to be explored. Use () if the problem has no dominance rule.
+    type Key: Eq + Hash;
+
+    // The objective value already guaranteed by a state.
+    fn value(&self, state: &Self::State) -> u64;
+
+    // An optimistic estimate of the best value reachable from a state. Must never underestimate.
+    fn upper_bound(&self, _state: &Self::State) -> u64 {
+        u64::MAX
+    }
+
+    // Passes each successor of a state to `push`. Taking a callback instead of returning a
+    // collection lets the search prune and store successors as they're generated, without copying
+    // them through a temporary buffer, which is noticeable in puzzles with lots of cheap states.
+    fn successors(&self, state: &Self::State, push: &mut impl FnMut(Self::State));
+
+    fn dominance_key(&self, _state: &Self::State) -> Option<Self::Key> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Order {
+    DepthFirst,
+    // Explores the states with the highest upper bound first.
+    BestFirst,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub order: Order,
+    pub bound_pruning: bool,
+    pub dominance_pruning: bool,
+    pub parallel: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            order: Order::DepthFirst,
+            bound_pruning: true,
+            dominance_pruning: true,
+            parallel: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Stats {
+    pub expanded: u64,
+    pub generated: u64,
+    pub pruned_by_bound: u64,
+    pub pruned_by_dominance: u64,
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.expanded += other.expanded;
+        self.generated += other.generated;
+        self.pruned_by_bound += other.pruned_by_bound;
+        self.pruned_by_dominance += other.pruned_by_dominance;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expanded {}, generated {}, pruned by bound {}, pruned by dominance {}",
+            self.expanded, self.generated, self.pruned_by_bound, self.pruned_by_dominance
+        )
+    }
+}
+
+pub struct Solution<S> {
+    pub value: u64,
+    // The state that reached the best value, if any state had a value above 0.
+    pub state: Option<S>,
+    pub stats: Stats,
+}
+
+// Finds the state with the maximum value reachable from `initial`.
+pub fn maximize<P>(problem: &P, initial: P::State, config: Config) -> Solution<P::State>
+where
+    P: Problem + Sync,
+    P::State: Clone + Send,
+{
+    if config.parallel {
+        maximize_in_parallel(problem, initial, config)
+    } else {
+        maximize_visiting(problem, initial, config, |_| {})
+    }
+}
+
+// Like `maximize`, but calls `visit` on every expanded state. Always runs sequentially.
+pub fn maximize_visiting<P>(
+    problem: &P,
+    initial: P::State,
+    config: Config,
+    mut visit: impl FnMut(&P::State),
+) -> Solution<P::State>
+where
+    P: Problem,
+    P::State: Clone,
+{
+    let incumbent = AtomicU64::new(0);
+    let mut searcher = Searcher::new(problem, config, &incumbent);
+    searcher.search(initial, &mut visit);
+    searcher.into_solution()
+}
+
+fn maximize_in_parallel<P>(problem: &P, initial: P::State, config: Config) -> Solution<P::State>
+where
+    P: Problem + Sync,
+    P::State: Clone + Send,
+{
+    // The incumbent value is shared so that every thread can prune with the best value found so
+    // far. Dominance maps are not shared though, so some dominated states may be explored twice.
+    let incumbent = AtomicU64::new(0);
+    let mut searcher = Searcher::new(problem, config, &incumbent);
+
+    // Split the search tree into enough subtrees to keep all threads busy.
+    let min_subtrees = rayon::current_num_threads() * 8;
+    let mut subtrees = VecDeque::from([searcher.bounded(initial)]);
+    while subtrees.len() < min_subtrees {
+        let Some((state, bound)) = subtrees.pop_front() else {
+            break;
+        };
+        searcher.expand(state, bound, &mut |_| {}, |s| subtrees.push_back(s));
+    }
+
+    let solutions: Vec<_> = subtrees
+        .into_par_iter()
+        .map(|(state, _bound)| {
+            let mut subtree_searcher = Searcher::new(problem, config, &incumbent);
+            subtree_searcher.search(state, &mut |_| {});
+            subtree_searcher.into_solution()
+        })
+        .collect();
+
+    let mut solution = searcher.into_solution();
+    for subtree_solution in solutions {
+        solution.stats += subtree_solution.stats;
+        if subtree_solution.value > solution.value {
+            solution.value = subtree_solution.value;
+            solution.state = subtree_solution.state;
+        }
+    }
+    solution
+}
+
+struct Searcher<'a, P: Problem> {
+    problem: &'a P,
+    config: Config,
+    incumbent: &'a AtomicU64,
+    best: Option<(u64, P::State)>,
+    best_values_by_key: HashMap<P::Key, u64>,
+    stats: Stats,
+}
+
+impl<'a, P> Searcher<'a, P>
+where
+    P: Problem,
+    P::State: Clone,
+{
+    fn new(problem: &'a P, config: Config, incumbent: &'a AtomicU64) -> Self {
+        Searcher {
+            problem,
+            config,
+            incumbent,
+            best: None,
+            best_values_by_key: HashMap::default(),
+            stats: Stats::default(),
+        }
+    }
+
+    fn search(&mut self, initial: P::State, visit: &mut impl FnMut(&P::State)) {
+        // Each kind of frontier gets its own copy of the search loop, so that pushing and popping
+        // states can be inlined into it.
+        match self.config.order {
+            Order::DepthFirst => self.search_with(Vec::new(), initial, visit),
+            Order::BestFirst => self.search_with(BinaryHeap::new(), initial, visit),
+        }
+    }
+
+    fn search_with(
+        &mut self,
+        mut frontier: impl Frontier<P::State>,
+        initial: P::State,
+        visit: &mut impl FnMut(&P::State),
+    ) {
+        frontier.push(self.bounded(initial));
+        while let Some((state, bound)) = frontier.pop() {
+            self.expand(state, bound, visit, |s| frontier.push(s));
+        }
+    }
+
+    fn bounded(&self, state: P::State) -> (P::State, u64) {
+        let bound = if self.config.bound_pruning {
+            self.problem.upper_bound(&state)
+        } else {
+            u64::MAX
+        };
+        (state, bound)
+    }
+
+    fn is_below_incumbent(&self, bound: u64) -> bool {
+        self.config.bound_pruning && bound <= self.incumbent.load(Relaxed)
+    }
+
+    // Updates the incumbent with a given state and passes its non-pruned successors to `push`.
+    fn expand(
+        &mut self,
+        state: P::State,
+        bound: u64,
+        visit: &mut impl FnMut(&P::State),
+        mut push: impl FnMut((P::State, u64)),
+    ) {
+        // The incumbent may have improved since this state was pushed.
+        if self.is_below_incumbent(bound) {
+            self.stats.pruned_by_bound += 1;
+            return;
+        }
+
+        self.stats.expanded += 1;
+        visit(&state);
+
+        let value = self.problem.value(&state);
+        if value > self.best.as_ref().map_or(0, |(v, _)| *v) {
+            self.incumbent.fetch_max(value, Relaxed);
+            self.best = Some((value, state.clone()));
+        }
+
+        let problem = self.problem;
+        problem.successors(&state, &mut |succ| {
+            self.stats.generated += 1;
+            let (succ, bound) = self.bounded(succ);
+            if self.is_below_incumbent(bound) {
+                self.stats.pruned_by_bound += 1;
+                return;
+            }
+            if self.is_dominated(&succ) {
+                self.stats.pruned_by_dominance += 1;
+                return;
+            }
+            push((succ, bound));
+        });
+    }
+
+    // Records the state's value for its dominance key, and returns whether another state with the
+    // same key and an equal or better value was already seen. Inlined so that problems without a
+    // dominance rule don't pay for a call on every generated state.
+    #[inline]
+    fn is_dominated(&mut self, state: &P::State) -> bool {
+        if !self.config.dominance_pruning {
+            return false;
+        }
+        let Some(key) = self.problem.dominance_key(state) else {
+            return false;
+        };
+        let value = self.problem.value(state);
+        self.record_dominance(key, value)
+    }
+
+    fn record_dominance(&mut self, key: P::Key, value: u64) -> bool {
+        match self.best_values_by_key.entry(key) {
+            Entry::Occupied(entry) if *entry.get() >= value => true,
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                false
+            }
+        }
+    }
+
+    fn into_solution(self) -> Solution<P::State> {
+        let (value, state) = match self.best {
+            Some((value, state)) => (value, Some(state)),
+            None => (0, None),
+        };
+        Solution {
+            value,
+            state,
+            stats: self.stats,
+        }
+    }
+}
+
+// The states waiting to be expanded, with their upper bounds.
+trait Frontier<S> {
+    fn push(&mut self, state_and_bound: (S, u64));
+    fn pop(&mut self) -> Option<(S, u64)>;
+}
+
+impl<S> Frontier<S> for Vec<(S, u64)> {
+    fn push(&mut self, state_and_bound: (S, u64)) {
+        Vec::push(self, state_and_bound);
+    }
+
+    fn pop(&mut self) -> Option<(S, u64)> {
+        Vec::pop(self)
+    }
+}
+
+impl<S> Frontier<S> for BinaryHeap<ByBound<S>> {
+    fn push(&mut self, (state, bound): (S, u64)) {
+        BinaryHeap::push(self, ByBound { state, bound });
+    }
+
+    fn pop(&mut self) -> Option<(S, u64)> {
+        BinaryHeap::pop(self).map(|b| (b.state, b.bound))
+    }
+}
+
+struct ByBound<S> {
+    state: S,
+    bound: u64,
+}
+
+impl<S> Ord for ByBound<S> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.bound.cmp(&other.bound)
+    }
+}
+
+impl<S> PartialOrd for ByBound<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S> PartialEq for ByBound<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bound == other.bound
+    }
+}
+
+impl<S> Eq for ByBound<S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, vecs};
+
+    // A 0/1 knapsack, small enough to be solved by brute force. States are the index of the next
+    // item to decide on, the weight taken so far, and the value taken so far.
+    struct Knapsack {
+        items: Vec<(u64, u64)>,
+        capacity: u64,
+    }
+
+    impl Problem for Knapsack {
+        type State = (usize, u64, u64);
+        type Key = (usize, u64);
+
+        fn value(&self, &(_, _, value): &Self::State) -> u64 {
+            value
+        }
+
+        fn upper_bound(&self, &(next_item, _, value): &Self::State) -> u64 {
+            value + self.items[next_item..].iter().map(|&(_, v)| v).sum::<u64>()
+        }
+
+        fn successors(
+            &self,
+            &(next_item, weight, value): &Self::State,
+            push: &mut impl FnMut(Self::State),
+        ) {
+            let Some(&(item_weight, item_value)) = self.items.get(next_item) else {
+                return;
+            };
+            push((next_item + 1, weight, value));
+            if weight + item_weight <= self.capacity {
+                push((next_item + 1, weight + item_weight, value + item_value));
+            }
+        }
+
+        fn dominance_key(&self, &(next_item, weight, _): &Self::State) -> Option<Self::Key> {
+            Some((next_item, weight))
+        }
+    }
+
+    impl Knapsack {
+        // Item values are derived from their weights so that a single list of numbers is enough
+        // to generate an instance.
+        fn new(weights: &[i64]) -> Knapsack {
+            let items: Vec<_> = weights
+                .iter()
+                .map(|&w| (w as u64, (w as u64 * 7) % 11 + 1))
+                .collect();
+            let capacity = items.iter().map(|&(w, _)| w).sum::<u64>() / 2;
+            Knapsack { items, capacity }
+        }
+
+        fn brute_force(&self) -> u64 {
+            (0..1_u32 << self.items.len())
+                .filter_map(|taken| {
+                    let taken_items = self
+                        .items
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| taken & (1 << i) != 0);
+                    let (weight, value) =
+                        taken_items.fold((0, 0), |(w, v), (_, &(iw, iv))| (w + iw, v + iv));
+                    (weight <= self.capacity).then_some(value)
+                })
+                .max()
+                .unwrap()
+        }
+    }
+
+    fn all_configs() -> Vec<Config> {
+        let mut configs = vec![];
+        for order in [Order::DepthFirst, Order::BestFirst] {
+            for bound_pruning in [false, true] {
+                for dominance_pruning in [false, true] {
+                    for parallel in [false, true] {
+                        configs.push(Config {
+                            order,
+                            bound_pruning,
+                            dominance_pruning,
+                            parallel,
+                        });
+                    }
+                }
+            }
+        }
+        configs
+    }
+
+    #[test]
+    fn all_configs_find_the_optimum() {
+        check(&vecs(ints(1..=30), 0..=12), |weights| {
+            let knapsack = Knapsack::new(weights);
+            let optimum = knapsack.brute_force();
+            all_configs().into_iter().all(|config| {
+                let solution = maximize(&knapsack, (0, 0, 0), config);
+                let state_value = solution.state.as_ref().map_or(0, |s| knapsack.value(s));
+                solution.value == optimum && state_value == optimum
+            })
+        });
+    }
+
+    #[test]
+    fn pruning_explores_fewer_states() {
+        let knapsack = Knapsack::new(&[12, 7, 25, 3, 18, 9, 30, 14, 5, 21, 11, 16]);
+        let expanded = |config| maximize(&knapsack, (0, 0, 0), config).stats.expanded;
+        let no_pruning = Config {
+            bound_pruning: false,
+            dominance_pruning: false,
+            ..Config::default()
+        };
+        // Without pruning, every feasible partial assignment is expanded.
+        assert_eq!(
+            expanded(no_pruning),
+            maximize(&knapsack, (0, 0, 0), no_pruning).stats.generated + 1
+        );
+        assert!(expanded(Config::default()) < expanded(no_pruning));
+        assert!(
+            expanded(Config {
+                order: Order::BestFirst,
+                ..Config::default()
+            }) < expanded(no_pruning)
+        );
+    }
+
+    #[test]
+    fn visits_every_expanded_state() {
+        let knapsack = Knapsack::new(&[4, 8, 15, 16, 23, 42]);
+        let mut visited = 0;
+        let solution = maximize_visiting(&knapsack, (0, 0, 0), Config::default(), |_| visited += 1);
+        assert_eq!(solution.value, knapsack.brute_force());
+        assert_eq!(visited, solution.stats.expanded);
+    }
+}
@@ -1,20 +1,239 @@
+use crate::rng::Rng;
+use crate::streaming;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::BufRead;
+use std::iter;
+
 pub fn run(input: &str) -> String {
-    let mut sorted_calories_by_elf: Vec<u32> = input
-        .split("\n\n")
-        .map(|elf_items| {
-            elf_items
-                .lines()
-                .map(|line| {
-                    line.parse::<u32>()
-                        .expect("each line should have a valid number of calories")
-                })
-                .sum()
+    run_streaming(&mut input.as_bytes())
+}
+
+// Only the top 3 elves are kept in memory, so the input doesn't need to be loaded.
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let mut top_calories = TopK::new(3);
+    for items in parse_elves(streaming::lines(input)) {
+        top_calories.push(items.iter().sum());
+    }
+    let top_calories = top_calories.into_sorted_vec();
+
+    let max_calories_on_single_elf = top_calories.first().expect("there should be some elf");
+    let max_calories_on_3_elves = top_calories.iter().sum::<u64>();
+
+    format!("{max_calories_on_single_elf} {max_calories_on_3_elves}")
+}
+
+// Answers other questions about the inventory, like `top:5`, `mean`, `median`, `percentile:90`
+// or `reach:100000`.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    let inventory = Inventory::parse(input);
+    let (name, arg) = match query.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (query, None),
+    };
+    let number_arg = || {
+        let arg = arg.ok_or_else(|| format!("query '{name}' needs a number, like '{name}:3'"))?;
+        arg.parse::<u64>()
+            .map_err(|_| format!("invalid number '{arg}' on query '{query}'"))
+    };
+    let no_elves = || "there are no elves".to_string();
+
+    match name {
+        "top" => {
+            let top = inventory.top(number_arg()? as usize);
+            let top_list: Vec<_> = top.iter().map(|c| c.to_string()).collect();
+            Ok(format!(
+                "{} (sum {})",
+                top_list.join(" "),
+                top.iter().sum::<u64>()
+            ))
+        }
+        "mean" => Ok(format!("{:.1}", inventory.mean().ok_or_else(no_elves)?)),
+        "median" => Ok(inventory.median().ok_or_else(no_elves)?.to_string()),
+        "percentile" => {
+            let percentile = number_arg()?;
+            if percentile > 100 {
+                return Err(format!(
+                    "percentile {percentile} should be between 0 and 100"
+                ));
+            }
+            let calories = inventory
+                .percentile(percentile as f64)
+                .ok_or_else(no_elves)?;
+            Ok(calories.to_string())
+        }
+        "reach" => {
+            let target = number_arg()?;
+            let elf_count = inventory.fewest_elves_reaching(target).ok_or_else(|| {
+                format!("all the elves together carry less than {target} calories")
+            })?;
+            Ok(elf_count.to_string())
+        }
+        _ => Err(format!(
+            "unknown query '{query}', try top:K, mean, median, percentile:P or reach:CALORIES"
+        )),
+    }
+}
+
+// The calories of every item that each elf is carrying.
+pub struct Inventory {
+    elves: Vec<Vec<u64>>,
+}
+
+impl Inventory {
+    pub fn parse(input: &str) -> Inventory {
+        Inventory {
+            elves: parse_elves(input.lines()).collect(),
+        }
+    }
+
+    pub fn elf_totals(&self) -> impl Iterator<Item = u64> + '_ {
+        self.elves.iter().map(|items| items.iter().sum())
+    }
+
+    // The k biggest elf totals, from biggest to smallest. O(n log k).
+    pub fn top(&self, k: usize) -> Vec<u64> {
+        let mut top = TopK::new(k);
+        for total in self.elf_totals() {
+            top.push(total);
+        }
+        top.into_sorted_vec()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.elves.is_empty() {
+            return None;
+        }
+        Some(self.elf_totals().sum::<u64>() as f64 / self.elves.len() as f64)
+    }
+
+    pub fn median(&self) -> Option<f64> {
+        let totals = self.sorted_totals();
+        let mid = totals.len() / 2;
+        match totals.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(totals[mid] as f64),
+            _ => Some((totals[mid - 1] + totals[mid]) as f64 / 2.0),
+        }
+    }
+
+    // The smallest elf total such that at least `p` percent of the elves carry that much or less.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        let totals = self.sorted_totals();
+        let rank = (p / 100.0 * totals.len() as f64).ceil() as usize;
+        totals.get(rank.saturating_sub(1)).copied()
+    }
+
+    // Taking the elves that carry the most first is the way to get to a target with fewest elves.
+    pub fn fewest_elves_reaching(&self, target: u64) -> Option<usize> {
+        let mut totals = self.sorted_totals();
+        totals.reverse();
+        let mut calories = 0;
+        for (i, total) in totals.iter().enumerate() {
+            if calories >= target {
+                return Some(i);
+            }
+            calories += total;
+        }
+        (calories >= target).then_some(totals.len())
+    }
+
+    fn sorted_totals(&self) -> Vec<u64> {
+        let mut totals: Vec<_> = self.elf_totals().collect();
+        totals.sort();
+        totals
+    }
+}
+
+// Groups lines into the items of each elf, which are separated by empty lines.
+fn parse_elves<S: AsRef<str>>(lines: impl Iterator<Item = S>) -> impl Iterator<Item = Vec<u64>> {
+    let mut lines = lines.peekable();
+    iter::from_fn(move || {
+        lines.peek()?;
+        let items = lines
+            .by_ref()
+            .take_while(|line| !line.as_ref().is_empty())
+            .map(|line| {
+                line.as_ref()
+                    .parse::<u64>()
+                    .expect("each line should have a valid number of calories")
+            })
+            .collect();
+        Some(items)
+    })
+}
+
+// Keeps the k biggest of the numbers pushed to it on a min-heap, so the smallest of them can be
+// replaced when a bigger one comes along.
+struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<u64>>,
+}
+
+impl TopK {
+    fn new(k: usize) -> TopK {
+        TopK {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1),
+        }
+    }
+
+    fn push(&mut self, n: u64) {
+        self.heap.push(Reverse(n));
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+
+    // The numbers from biggest to smallest.
+    fn into_sorted_vec(self) -> Vec<u64> {
+        // The heap's sorted vec is in ascending order of Reverse, so the biggest numbers come
+        // first.
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(n)| n)
+            .collect()
+    }
+}
+
+// Generates the inventories of `size` elves.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let elves: Vec<String> = (0..size.max(1))
+        .map(|_| {
+            let items: Vec<_> = (0..rng.range(1..=15))
+                .map(|_| rng.range(1000..=10_000).to_string())
+                .collect();
+            items.join("\n")
         })
         .collect();
-    sorted_calories_by_elf.sort();
+    elves.join("\n\n") + "\n"
+}
 
-    let max_calories_on_single_elf = *sorted_calories_by_elf.last().unwrap();
-    let max_calories_on_3_elves = sorted_calories_by_elf.iter().rev().take(3).sum::<u32>();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    format!("{max_calories_on_single_elf} {max_calories_on_3_elves}")
+    const SAMPLE: &str = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000\n";
+
+    #[test]
+    fn sample_queries() {
+        let answer = |q| query(SAMPLE, q);
+        assert_eq!(answer("top:2"), Ok("24000 11000 (sum 35000)".to_string()));
+        assert_eq!(
+            answer("top:9"),
+            Ok("24000 11000 10000 6000 4000 (sum 55000)".to_string())
+        );
+        assert_eq!(answer("mean"), Ok("11000.0".to_string()));
+        assert_eq!(answer("median"), Ok("10000".to_string()));
+        assert_eq!(answer("percentile:50"), Ok("10000".to_string()));
+        assert_eq!(answer("percentile:90"), Ok("24000".to_string()));
+        assert_eq!(answer("percentile:0"), Ok("4000".to_string()));
+        assert_eq!(answer("reach:0"), Ok("0".to_string()));
+        assert_eq!(answer("reach:24001"), Ok("2".to_string()));
+        assert_eq!(answer("reach:55000"), Ok("5".to_string()));
+        assert!(answer("reach:55001").is_err());
+        assert!(answer("top").is_err());
+        assert!(answer("nope").is_err());
+    }
 }
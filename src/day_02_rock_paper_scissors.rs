@@ -1,59 +1,553 @@
+use crate::rng::Rng;
+use crate::streaming;
+use std::fs;
+use std::io::BufRead;
+
 pub fn run(input: &str) -> String {
-    let total_score: u32 = input
-        .lines()
-        .map(|line| {
-            let opponent_shape = parse_shape(line.as_bytes()[0]);
-            let own_shape = parse_shape(line.as_bytes()[2]);
-            own_shape as u32 + get_round_outcome_score(opponent_shape, own_shape)
+    run_streaming(&mut input.as_bytes())
+}
+
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let game = Game::rock_paper_scissors();
+    let mut total_score = 0;
+    let mut total_score_2 = 0;
+
+    for line in streaming::lines(input) {
+        let (score, score_2) = game
+            .score_round(&line)
+            .unwrap_or_else(|err| panic!("invalid round '{line}': {err}"));
+        total_score += score;
+        total_score_2 += score_2;
+    }
+
+    format!("{total_score} {total_score_2}")
+}
+
+// Answers other questions about the strategy guide:
+// - `rules:FILE` plays it with the rules of another game.
+// - `decoders` tries every meaning the second column letters could have (see `decoder_report`).
+//   `decoders:FILE` does the same for another game.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    let (name, rules_path) = match query.split_once(':') {
+        Some((name, path)) => (name, Some(path)),
+        None => (query, None),
+    };
+    let game = match rules_path {
+        Some(path) => {
+            let rules = fs::read_to_string(path)
+                .map_err(|err| format!("error reading rules {path}: {err}"))?;
+            Game::parse(&rules)?
+        }
+        None => Game::rock_paper_scissors(),
+    };
+
+    match name {
+        "rules" if rules_path.is_some() => {
+            let mut total_score = 0;
+            let mut total_score_2 = 0;
+            for line in input.lines() {
+                let (score, score_2) = game
+                    .score_round(line)
+                    .map_err(|err| format!("invalid round '{line}': {err}"))?;
+                total_score += score;
+                total_score_2 += score_2;
+            }
+            Ok(format!("{total_score} {total_score_2}"))
+        }
+        "decoders" => decoder_report(&game, input),
+        _ => Err(format!(
+            "unknown query '{query}', try rules:FILE, decoders or decoders:FILE"
+        )),
+    }
+}
+
+// The puzzle is about guessing what the second column means, but only checks the two meanings it
+// reveals. This tries all of them: every way of mapping each letter to a different shape, and to a
+// different outcome, and reports the best, worst and expected (mean) total score of each
+// interpretation. The letters used against each opponent shape are summed up too, as a guide
+// that was made up by someone who knows what they're doing won't pick them uniformly.
+fn decoder_report(game: &Game, input: &str) -> Result<String, String> {
+    let mut rounds = vec![];
+    for line in input.lines() {
+        let [opponent_letter, b' ', letter] = line.as_bytes()[..] else {
+            return Err(format!("invalid round '{line}'"));
+        };
+        rounds.push((
+            game.opponent_shape(opponent_letter as char)?,
+            letter as char,
+        ));
+    }
+    let mut letters: Vec<_> = rounds.iter().map(|&(_, letter)| letter).collect();
+    letters.sort();
+    letters.dedup();
+
+    // counts[opponent shape][letter index] is how many rounds have that combination.
+    let mut counts = vec![vec![0; letters.len()]; game.shapes.len()];
+    for (opponent_shape, letter) in rounds {
+        let letter_index = letters.iter().position(|&l| l == letter).unwrap();
+        counts[opponent_shape][letter_index] += 1;
+    }
+
+    let mut lines = vec![
+        summarize_mappings(
+            "As shapes",
+            &letters,
+            &counts,
+            game.shapes.len(),
+            |shape| game.shapes[shape].name.clone(),
+            |opponent_shape, own_shape| game.shape_score(opponent_shape, own_shape),
+        ),
+        summarize_mappings(
+            "As outcomes",
+            &letters,
+            &counts,
+            OUTCOMES.len(),
+            |outcome| format!("{:?}", OUTCOMES[outcome]),
+            |opponent_shape, outcome| game.outcome_score(opponent_shape, OUTCOMES[outcome]),
+        ),
+    ];
+    for (shape, letter_counts) in game.shapes.iter().zip(counts.iter()) {
+        let rounds: u64 = letter_counts.iter().sum();
+        if rounds == 0 {
+            continue;
+        }
+        let percentages: Vec<_> = letters
+            .iter()
+            .zip(letter_counts)
+            .map(|(letter, &count)| {
+                format!("{letter} {:.1}%", count as f64 * 100.0 / rounds as f64)
+            })
+            .collect();
+        lines.push(format!(
+            "{} ({}): {rounds} rounds; {}",
+            shape.opponent_letter,
+            shape.name,
+            percentages.join(", ")
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn summarize_mappings(
+    interpretation: &str,
+    letters: &[char],
+    counts: &[Vec<u64>],
+    targets: usize,
+    target_name: impl Fn(usize) -> String,
+    score: impl Fn(usize, usize) -> u64,
+) -> String {
+    if letters.len() > targets {
+        return format!(
+            "{interpretation}: not possible, there are {} letters for {targets} meanings",
+            letters.len()
+        );
+    }
+    let mappings = injective_mappings(letters.len(), targets);
+    let scores: Vec<u64> = mappings
+        .iter()
+        .map(|mapping| {
+            let mut total_score = 0;
+            for (opponent_shape, letter_counts) in counts.iter().enumerate() {
+                for (&count, &target) in letter_counts.iter().zip(mapping) {
+                    total_score += count * score(opponent_shape, target);
+                }
+            }
+            total_score
         })
-        .sum();
-
-    let total_score_2: u32 = input
-        .lines()
-        .map(|line| {
-            let opponent_shape = parse_shape(line.as_bytes()[0]);
-            let round_result = match line.as_bytes()[2] {
-                b'X' => LOSE,
-                b'Y' => DRAW,
-                b'Z' => WIN,
-                ch => unreachable!("unexpected character '{}'", ch as char),
+        .collect();
+
+    let describe = |i: usize| {
+        let pairs: Vec<_> = letters
+            .iter()
+            .zip(&mappings[i])
+            .map(|(letter, &target)| format!("{letter}={}", target_name(target)))
+            .collect();
+        format!("{} ({})", scores[i], pairs.join(" "))
+    };
+    let best = (0..scores.len()).max_by_key(|&i| scores[i]).unwrap();
+    let worst = (0..scores.len()).min_by_key(|&i| scores[i]).unwrap();
+    let expected = scores.iter().sum::<u64>() as f64 / scores.len() as f64;
+    format!(
+        "{interpretation}: best {}, worst {}, expected {expected:.1} over {} mappings",
+        describe(best),
+        describe(worst),
+        scores.len()
+    )
+}
+
+// Every way of giving each of `len` items a different target out of `targets`.
+fn injective_mappings(len: usize, targets: usize) -> Vec<Vec<usize>> {
+    if len == 0 {
+        return vec![vec![]];
+    }
+    let mut mappings = vec![];
+    for mapping in injective_mappings(len - 1, targets) {
+        for target in (0..targets).filter(|target| !mapping.contains(target)) {
+            let mut extended = mapping.clone();
+            extended.push(target);
+            mappings.push(extended);
+        }
+    }
+    mappings
+}
+
+const ROCK_PAPER_SCISSORS_RULES: &str = "\
+shape Rock 1 A X
+shape Paper 2 B Y
+shape Scissors 3 C Z
+outcome lose 0 X
+outcome draw 3 Y
+outcome win 6 Z
+Rock beats Scissors
+Paper beats Rock
+Scissors beats Paper
+";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Lose,
+    Draw,
+    Win,
+}
+use Outcome::*;
+
+const OUTCOMES: [Outcome; 3] = [Lose, Draw, Win];
+
+struct Shape {
+    name: String,
+    score: u64,
+    // The letters for this shape on the first and second columns of the strategy guide.
+    opponent_letter: char,
+    own_letter: char,
+}
+
+// A hand game like Rock Paper Scissors, with any number of shapes.
+pub struct Game {
+    shapes: Vec<Shape>,
+    // beats[a][b] is whether shape a beats shape b.
+    beats: Vec<Vec<bool>>,
+    outcome_scores: [u64; 3],
+    // The letters for each outcome on the second column, when it says how the round must end.
+    outcome_letters: [char; 3],
+}
+
+impl Game {
+    pub fn rock_paper_scissors() -> Game {
+        Game::parse(ROCK_PAPER_SCISSORS_RULES).expect("rock paper scissors rules should be valid")
+    }
+
+    pub fn builder() -> GameBuilder {
+        GameBuilder::default()
+    }
+
+    // Parses rules with one definition per line, like:
+    //
+    //   shape Rock 1 A X       (name, score, opponent letter and own letter)
+    //   outcome win 6 Z        (lose, draw or win, score and letter)
+    //   Rock beats Scissors
+    //
+    // Empty lines and lines starting with # are ignored.
+    pub fn parse(rules: &str) -> Result<Game, String> {
+        let mut builder = Game::builder();
+        for (line_index, line) in rules.lines().enumerate() {
+            let line_error = |message: &str| format!("line {}: {message}", line_index + 1);
+            let parse_score = |s: &str| s.parse().map_err(|_| line_error("invalid score"));
+            let parse_letter = |s: &str| match s.as_bytes() {
+                [letter] => Ok(*letter as char),
+                _ => Err(line_error("letters should be a single character")),
             };
-            let own_shape = [Rock, Paper, Scissors]
-                .into_iter()
-                .find(|&shape| get_round_outcome_score(opponent_shape, shape) == round_result)
-                .expect("there must exist a shape to have round_result against the opponent_shape");
-            own_shape as u32 + round_result
-        })
-        .sum();
+            let words: Vec<_> = line.split_whitespace().collect();
+            builder = match words[..] {
+                [] => builder,
+                [first, ..] if first.starts_with('#') => builder,
+                ["shape", name, score, opponent_letter, own_letter] => builder.shape(
+                    name,
+                    parse_score(score)?,
+                    parse_letter(opponent_letter)?,
+                    parse_letter(own_letter)?,
+                ),
+                ["outcome", outcome, score, letter] => {
+                    let outcome = match outcome {
+                        "lose" => Lose,
+                        "draw" => Draw,
+                        "win" => Win,
+                        _ => return Err(line_error("outcome should be lose, draw or win")),
+                    };
+                    builder.outcome(outcome, parse_score(score)?, parse_letter(letter)?)
+                }
+                [winner, "beats", loser] => builder.beats(winner, loser),
+                _ => return Err(line_error(&format!("invalid rule '{line}'"))),
+            };
+        }
+        builder.build()
+    }
 
-    format!("{total_score} {total_score_2}")
+    fn outcome(&self, opponent_shape: usize, own_shape: usize) -> Outcome {
+        if self.beats[own_shape][opponent_shape] {
+            Win
+        } else if self.beats[opponent_shape][own_shape] {
+            Lose
+        } else {
+            Draw
+        }
+    }
+
+    // Scores a strategy guide line in both ways: taking the second column as the shape to play,
+    // and as the outcome the round must have.
+    pub fn score_round(&self, line: &str) -> Result<(u64, u64), String> {
+        let [opponent_letter, b' ', own_letter] = line.as_bytes()[..] else {
+            return Err("rounds should be two letters separated by a space".to_string());
+        };
+        let (opponent_letter, own_letter) = (opponent_letter as char, own_letter as char);
+        Ok((
+            self.score_by_shape(opponent_letter, own_letter)?,
+            self.score_by_outcome(opponent_letter, own_letter)?,
+        ))
+    }
+
+    pub fn score_by_shape(&self, opponent_letter: char, own_letter: char) -> Result<u64, String> {
+        let opponent_shape = self.opponent_shape(opponent_letter)?;
+        let own_shape = self
+            .shapes
+            .iter()
+            .position(|shape| shape.own_letter == own_letter)
+            .ok_or_else(|| format!("no shape for letter '{own_letter}'"))?;
+        Ok(self.shape_score(opponent_shape, own_shape))
+    }
+
+    pub fn score_by_outcome(
+        &self,
+        opponent_letter: char,
+        outcome_letter: char,
+    ) -> Result<u64, String> {
+        let opponent_shape = self.opponent_shape(opponent_letter)?;
+        let outcome = OUTCOMES
+            .into_iter()
+            .find(|&outcome| self.outcome_letters[outcome as usize] == outcome_letter)
+            .ok_or_else(|| format!("no outcome for letter '{outcome_letter}'"))?;
+        Ok(self.outcome_score(opponent_shape, outcome))
+    }
+
+    fn shape_score(&self, opponent_shape: usize, own_shape: usize) -> u64 {
+        let outcome = self.outcome(opponent_shape, own_shape);
+        self.shapes[own_shape].score + self.outcome_scores[outcome as usize]
+    }
+
+    // If more than one shape gives the outcome, as can happen on games with more than 3 shapes,
+    // the one with the highest score is played.
+    fn outcome_score(&self, opponent_shape: usize, outcome: Outcome) -> u64 {
+        let own_shape_score = (0..self.shapes.len())
+            .filter(|&own_shape| self.outcome(opponent_shape, own_shape) == outcome)
+            .map(|own_shape| self.shapes[own_shape].score)
+            .max()
+            .expect("validated games have shapes for every outcome");
+        own_shape_score + self.outcome_scores[outcome as usize]
+    }
+
+    fn opponent_shape(&self, opponent_letter: char) -> Result<usize, String> {
+        self.shapes
+            .iter()
+            .position(|shape| shape.opponent_letter == opponent_letter)
+            .ok_or_else(|| format!("no shape for letter '{opponent_letter}'"))
+    }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-enum Shape {
-    Rock = 1,
-    Paper = 2,
-    Scissors = 3,
+#[derive(Default)]
+pub struct GameBuilder {
+    shapes: Vec<Shape>,
+    beats: Vec<(String, String)>,
+    outcomes: [Option<(u64, char)>; 3],
 }
-use Shape::*;
 
-const LOSE: u32 = 0;
-const DRAW: u32 = 3;
-const WIN: u32 = 6;
+impl GameBuilder {
+    pub fn shape(
+        mut self,
+        name: &str,
+        score: u64,
+        opponent_letter: char,
+        own_letter: char,
+    ) -> Self {
+        self.shapes.push(Shape {
+            name: name.to_string(),
+            score,
+            opponent_letter,
+            own_letter,
+        });
+        self
+    }
+
+    pub fn beats(mut self, winner: &str, loser: &str) -> Self {
+        self.beats.push((winner.to_string(), loser.to_string()));
+        self
+    }
+
+    pub fn outcome(mut self, outcome: Outcome, score: u64, letter: char) -> Self {
+        self.outcomes[outcome as usize] = Some((score, letter));
+        self
+    }
+
+    // Checks that the rules make sense: every pair of different shapes is decided by exactly one
+    // "beats" rule, and every shape can be won, lost and drawn against.
+    pub fn build(self) -> Result<Game, String> {
+        let shapes = self.shapes;
+        let names: Vec<_> = shapes.iter().map(|s| &s.name[..]).collect();
+        let has_duplicates = |items: Vec<String>| {
+            let mut sorted = items.clone();
+            sorted.sort();
+            sorted.dedup();
+            sorted.len() != items.len()
+        };
+        if has_duplicates(names.iter().map(|name| name.to_string()).collect()) {
+            return Err("shape names should be unique".to_string());
+        }
+        if has_duplicates(
+            shapes
+                .iter()
+                .map(|s| s.opponent_letter.to_string())
+                .collect(),
+        ) || has_duplicates(shapes.iter().map(|s| s.own_letter.to_string()).collect())
+        {
+            return Err("shape letters should be unique on each column".to_string());
+        }
+
+        let mut outcome_scores = [0; 3];
+        let mut outcome_letters = [' '; 3];
+        for outcome in OUTCOMES {
+            let Some((score, letter)) = self.outcomes[outcome as usize] else {
+                return Err(format!("missing score and letter for outcome {outcome:?}"));
+            };
+            outcome_scores[outcome as usize] = score;
+            outcome_letters[outcome as usize] = letter;
+        }
+        if has_duplicates(outcome_letters.iter().map(|l| l.to_string()).collect()) {
+            return Err("outcome letters should be unique".to_string());
+        }
+
+        let shape_index = |name: &str| {
+            names
+                .iter()
+                .position(|&n| n == name)
+                .ok_or_else(|| format!("unknown shape '{name}'"))
+        };
+        let mut beats = vec![vec![false; shapes.len()]; shapes.len()];
+        for (winner, loser) in self.beats.iter() {
+            let (winner, loser) = (shape_index(winner)?, shape_index(loser)?);
+            if winner == loser {
+                return Err(format!("{} can't beat itself", names[winner]));
+            }
+            if beats[winner][loser] {
+                return Err(format!(
+                    "{} beats {} is given more than once",
+                    names[winner], names[loser]
+                ));
+            }
+            beats[winner][loser] = true;
+        }
+        for a in 0..shapes.len() {
+            for b in a + 1..shapes.len() {
+                match (beats[a][b], beats[b][a]) {
+                    (true, true) => {
+                        return Err(format!("{} and {} beat each other", names[a], names[b]))
+                    }
+                    (false, false) => {
+                        return Err(format!("{} vs {} is not decided", names[a], names[b]))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (a, name) in names.iter().enumerate() {
+            if !(0..shapes.len()).any(|b| beats[a][b]) {
+                return Err(format!("{name} should beat some shape"));
+            }
+            if !(0..shapes.len()).any(|b| beats[b][a]) {
+                return Err(format!("{name} should be beaten by some shape"));
+            }
+        }
 
-fn get_round_outcome_score(opponent_shape: Shape, own_shape: Shape) -> u32 {
-    match (opponent_shape, own_shape) {
-        (Rock, Paper) | (Paper, Scissors) | (Scissors, Rock) => WIN,
-        (a, b) if a == b => DRAW,
-        _ => LOSE,
+        Ok(Game {
+            shapes,
+            beats,
+            outcome_scores,
+            outcome_letters,
+        })
     }
 }
 
-fn parse_shape(char: u8) -> Shape {
-    match char {
-        b'A' | b'X' => Rock,
-        b'B' | b'Y' => Paper,
-        b'C' | b'Z' => Scissors,
-        ch => unreachable!("unexpected character '{}'", ch as char),
+// Generates a strategy guide of `size` rounds.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            format!(
+                "{} {}\n",
+                *rng.choose(b"ABC") as char,
+                *rng.choose(b"XYZ") as char
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rock_paper_scissors_lizard_spock() {
+        let rules = fs::read_to_string("rules/rock_paper_scissors_lizard_spock.txt").unwrap();
+        let game = Game::parse(&rules).unwrap();
+        // Spock vaporizes Rock, Scissors decapitates Lizard, and Lizard vs Lizard is a draw.
+        assert_eq!(game.score_by_shape('A', 'Z'), Ok(5 + 6));
+        assert_eq!(game.score_by_shape('D', 'X'), Ok(3 + 6));
+        assert_eq!(game.score_by_shape('D', 'Y'), Ok(4 + 3));
+        // Both Paper and Spock beat Rock, so Spock is played. Lizard and Scissors lose to Rock.
+        assert_eq!(game.score_by_outcome('A', 'Z'), Ok(5 + 6));
+        assert_eq!(game.score_by_outcome('A', 'X'), Ok(4));
+        assert_eq!(game.score_round("E Y"), Ok((4 + 6, 5 + 3)));
+        assert!(game.score_round("E V").is_err());
+    }
+
+    #[test]
+    fn decoders() {
+        let report = query("A Y\nB X\nC Z", "decoders").unwrap();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(
+            lines[0],
+            "As shapes: best 24 (X=Scissors Y=Paper Z=Rock), \
+            worst 6 (X=Rock Y=Scissors Z=Paper), expected 15.0 over 6 mappings"
+        );
+        assert_eq!(
+            lines[1],
+            "As outcomes: best 18 (X=Win Y=Lose Z=Draw), \
+            worst 12 (X=Lose Y=Draw Z=Win), expected 15.0 over 6 mappings"
+        );
+        assert_eq!(lines[2], "A (Rock): 1 rounds; X 0.0%, Y 100.0%, Z 0.0%");
+    }
+
+    #[test]
+    fn inconsistent_rules() {
+        let rules = format!("{ROCK_PAPER_SCISSORS_RULES}Rock beats Paper\n");
+        assert_eq!(
+            Game::parse(&rules).err(),
+            Some("Rock and Paper beat each other".to_string())
+        );
+        let rules = ROCK_PAPER_SCISSORS_RULES.replace("Paper beats Rock", "");
+        assert_eq!(
+            Game::parse(&rules).err(),
+            Some("Rock vs Paper is not decided".to_string())
+        );
+        let rules = format!("{ROCK_PAPER_SCISSORS_RULES}Paper beats Rock\n");
+        assert_eq!(
+            Game::parse(&rules).err(),
+            Some("Paper beats Rock is given more than once".to_string())
+        );
+        let game = Game::builder()
+            .shape("Rock", 1, 'A', 'X')
+            .shape("Paper", 2, 'B', 'Y')
+            .beats("Paper", "Rock")
+            .outcome(Lose, 0, 'X')
+            .outcome(Draw, 3, 'Y')
+            .outcome(Win, 6, 'Z')
+            .build();
+        assert_eq!(game.err(), Some("Rock should beat some shape".to_string()));
     }
 }
@@ -1,42 +1,232 @@
+use crate::bitset::Bitset;
+use crate::rng::Rng;
+use crate::streaming;
+use std::io::BufRead;
+
 pub fn run(input: &str) -> String {
-    let lines: Vec<_> = input.lines().collect();
-    let shared_items_priority_sum: u32 = lines
-        .iter()
-        .map(|line| {
-            let rucksack_items = line.as_bytes();
-            let (first_compartment, second_compartment) =
-                rucksack_items.split_at(rucksack_items.len() / 2);
-            let shared_item_type = *first_compartment
-                .iter()
-                .find(|item_type| second_compartment.contains(item_type))
-                .expect("there should be a shared item type between compartments");
-            priority_for_item_type(shared_item_type) as u32
-        })
-        .sum();
-
-    let group_badges_priority_sum: u32 = lines
-        .chunks(3)
-        .map(|elf_group| {
-            let elf_1_rucksack = elf_group[0].as_bytes();
-            let elf_2_rucksack = elf_group[1].as_bytes();
-            let elf_3_rucksack = elf_group[2].as_bytes();
-            let badge = *elf_1_rucksack
-                .iter()
-                .find(|item_type| {
-                    elf_2_rucksack.contains(item_type) && elf_3_rucksack.contains(item_type)
-                })
-                .expect("there should be a shared item type between the 3 elves");
-            priority_for_item_type(badge) as u32
-        })
-        .sum();
+    run_streaming(&mut input.as_bytes())
+}
 
+// Only the rucksacks of the current elf group are kept in memory.
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let (shared_items_priority_sum, group_badges_priority_sum) =
+        priority_sums(streaming::lines(input), 3).unwrap_or_else(|err| panic!("{err}"));
     format!("{shared_items_priority_sum} {group_badges_priority_sum}")
 }
 
-fn priority_for_item_type(item_type: u8) -> u8 {
+// Answers other questions about the rucksacks:
+// - `group:N` sums the badge priorities of groups of N elves instead of 3.
+// - `report` lists every item type shared between the compartments of each rucksack and between
+//   the rucksacks of each group, without failing when there are none or several.
+//   `report:N` does the same for groups of N elves.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    let (name, group_size) = match query.split_once(':') {
+        Some((name, group_size)) => {
+            let group_size = group_size
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("invalid group size '{group_size}'"))?;
+            (name, group_size)
+        }
+        None => (query, 3),
+    };
+    match name {
+        "group" if query.contains(':') => {
+            let (_, group_badges_priority_sum) =
+                priority_sums(input.lines().map(str::to_string), group_size)?;
+            Ok(group_badges_priority_sum.to_string())
+        }
+        "report" => shared_items_report(input, group_size),
+        _ => Err(format!(
+            "unknown query '{query}', try group:N, report or report:N"
+        )),
+    }
+}
+
+// A set of item types, as a bitset of their priorities.
+type ItemSet = u64;
+
+fn priority_sums(
+    lines: impl Iterator<Item = String>,
+    group_size: usize,
+) -> Result<(u64, u64), String> {
+    let mut shared_items_priority_sum = 0;
+    let mut group_badges_priority_sum = 0;
+    let mut elf_group = Vec::with_capacity(group_size);
+
+    for (line_index, line) in lines.enumerate() {
+        let line_error = |err| format!("rucksack on line {}: {err}", line_index + 1);
+        let (first_compartment, second_compartment) = compartments(&line).map_err(line_error)?;
+        let shared_item_type = single_item_type(&[first_compartment, second_compartment])
+            .map_err(|err| line_error(format!("{err} between compartments")))?;
+        shared_items_priority_sum += shared_item_type as u64;
+
+        elf_group.push(first_compartment.union(&second_compartment));
+        if elf_group.len() == group_size {
+            let badge = single_item_type(&elf_group)
+                .map_err(|err| format!("group ending on line {}: {err}", line_index + 1))?;
+            group_badges_priority_sum += badge as u64;
+            elf_group.clear();
+        }
+    }
+    if !elf_group.is_empty() {
+        return Err(format!(
+            "the last group only has {} of {group_size} rucksacks",
+            elf_group.len()
+        ));
+    }
+
+    Ok((shared_items_priority_sum, group_badges_priority_sum))
+}
+
+fn shared_items_report(input: &str, group_size: usize) -> Result<String, String> {
+    let rucksacks = input
+        .lines()
+        .enumerate()
+        .map(|(line_index, line)| {
+            compartments(line).map_err(|err| format!("rucksack on line {}: {err}", line_index + 1))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut lines = vec![];
+    for (i, (first_compartment, second_compartment)) in rucksacks.iter().enumerate() {
+        let shared = first_compartment.intersection(second_compartment);
+        lines.push(format!("rucksack {}: {}", i + 1, describe_item_set(shared)));
+    }
+    for (i, group) in rucksacks.chunks(group_size).enumerate() {
+        let shared = group
+            .iter()
+            .map(|(first, second)| first.union(second))
+            .reduce(|a, b| a.intersection(&b))
+            .unwrap();
+        lines.push(format!("group {}: {}", i + 1, describe_item_set(shared)));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn compartments(rucksack: &str) -> Result<(ItemSet, ItemSet), String> {
+    let rucksack_items = rucksack.as_bytes();
+    if !rucksack_items.len().is_multiple_of(2) {
+        return Err("compartments should have the same number of items".to_string());
+    }
+    let (first_compartment, second_compartment) = rucksack_items.split_at(rucksack_items.len() / 2);
+    Ok((item_set(first_compartment)?, item_set(second_compartment)?))
+}
+
+fn item_set(items: &[u8]) -> Result<ItemSet, String> {
+    let mut set = ItemSet::default();
+    for &item_type in items {
+        set.insert(priority_for_item_type(item_type)? as usize);
+    }
+    Ok(set)
+}
+
+// Returns the priority of the only item type all the sets have in common.
+fn single_item_type(sets: &[ItemSet]) -> Result<u8, String> {
+    let shared = sets.iter().fold(ItemSet::MAX, |a, b| a.intersection(b));
+    match shared.len() {
+        1 => Ok(shared.trailing_zeros() as u8),
+        0 => Err("no shared item type".to_string()),
+        _ => Err(format!(
+            "several shared item types ({})",
+            describe_item_set(shared)
+        )),
+    }
+}
+
+fn describe_item_set(set: ItemSet) -> String {
+    if set.is_empty() {
+        return "none".to_string();
+    }
+    set.iter()
+        .map(|priority| item_type_for_priority(priority as u8) as char)
+        .collect()
+}
+
+fn priority_for_item_type(item_type: u8) -> Result<u8, String> {
     match item_type {
-        b'a'..=b'z' => item_type - b'a' + 1,
-        b'A'..=b'Z' => item_type - b'A' + 27,
-        _ => unreachable!("invalid item type {}", item_type as char),
+        b'a'..=b'z' => Ok(item_type - b'a' + 1),
+        b'A'..=b'Z' => Ok(item_type - b'A' + 27),
+        _ => Err(format!("invalid item type '{}'", item_type as char)),
+    }
+}
+
+fn item_type_for_priority(priority: u8) -> u8 {
+    match priority {
+        1..=26 => priority - 1 + b'a',
+        _ => priority - 27 + b'A',
+    }
+}
+
+// Generates `size` groups of 3 rucksacks. Each rucksack has exactly one item type in both
+// compartments, and each group has exactly one item type in common, its badge.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let mut item_types: Vec<u8> = (b'a'..=b'z').chain(b'A'..=b'Z').collect();
+    let mut output = String::new();
+    for _ in 0..size.max(1) {
+        rng.shuffle(&mut item_types);
+        let (badge, other_item_types) = item_types.split_first().unwrap();
+        // Each elf of the group gets its own item types, so the badge is the only shared one.
+        for elf_item_types in other_item_types.chunks(other_item_types.len() / 3).take(3) {
+            let (shared_item, other_items) = elf_item_types.split_first().unwrap();
+            let (first_items, second_items) = other_items.split_at(other_items.len() / 2);
+            let compartment_size = rng.range(2..=16) as usize;
+            let mut compartments = [first_items, second_items].map(|items| {
+                let mut compartment = vec![*shared_item];
+                compartment.extend((1..compartment_size).map(|_| *rng.choose(items)));
+                compartment
+            });
+            let badge_compartment = rng.below(2);
+            let badge_index = rng.range(1..=compartment_size as i64 - 1) as usize;
+            compartments[badge_compartment][badge_index] = *badge;
+            for compartment in compartments.iter_mut() {
+                rng.shuffle(compartment);
+                output.extend(compartment.iter().map(|&item| item as char));
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "vJrwpWtwJgWrhcsFMMfFFhFp\n\
+        jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL\n\
+        PmmdzqPrVvPwwTWBwg\n\
+        wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn\n\
+        ttgJtRGJQctTZtZT\n\
+        CrZsJsPPZsGzwwsLwLmpwMDw\n";
+
+    #[test]
+    fn sample_queries() {
+        let answer = |q| query(SAMPLE, q);
+        assert_eq!(answer("group:3"), Ok("70".to_string()));
+        assert_eq!(
+            answer("group:6"),
+            Err("group ending on line 6: no shared item type".to_string())
+        );
+        assert_eq!(
+            answer("group:2"),
+            Err("group ending on line 2: several shared item types (frsFM)".to_string())
+        );
+        let first_4_rucksacks = SAMPLE.lines().take(4).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            query(&first_4_rucksacks, "group:3"),
+            Err("the last group only has 1 of 3 rucksacks".to_string())
+        );
+        assert_eq!(
+            answer("report:2"),
+            Ok(
+                "rucksack 1: p\nrucksack 2: L\nrucksack 3: P\nrucksack 4: v\nrucksack 5: t\n\
+                rucksack 6: s\ngroup 1: frsFM\ngroup 2: qvwBT\ngroup 3: GJZ"
+                    .to_string()
+            )
+        );
+        assert!(answer("group:0").is_err());
+        assert!(answer("nope").is_err());
     }
 }
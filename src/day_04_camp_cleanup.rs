@@ -1,15 +1,18 @@
+use crate::rng::Rng;
+use crate::streaming;
+use std::io::BufRead;
+
 pub fn run(input: &str) -> String {
-    let mut fully_overlapping_pairs = 0;
-    let mut overlapping_pairs = 0;
+    run_streaming(&mut input.as_bytes())
+}
 
-    for line in input.lines() {
-        let numbers: Vec<u32> = line
-            .split(&['-', ','])
-            .map(|s| s.parse().expect("section IDs should be numbers"))
-            .collect();
-        let [elf_1_start, elf_1_end, elf_2_start, elf_2_end] = numbers[..] else {
-            panic!("invalid line format {line}");
-        };
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let mut fully_overlapping_pairs: u64 = 0;
+    let mut overlapping_pairs: u64 = 0;
+
+    for line in streaming::lines(input) {
+        let [(elf_1_start, elf_1_end), (elf_2_start, elf_2_end)] =
+            parse_line(&line).unwrap_or_else(|err| panic!("{err}"));
 
         if (elf_1_start >= elf_2_start && elf_1_end <= elf_2_end)
             || (elf_2_start >= elf_1_start && elf_2_end <= elf_1_end)
@@ -24,3 +27,231 @@ pub fn run(input: &str) -> String {
 
     format!("{fully_overlapping_pairs} {overlapping_pairs}")
 }
+
+// Entry point for fuzzing the assignments parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    for line in input.lines() {
+        let _ = parse_line(line);
+    }
+}
+
+// The first and last section IDs assigned to an elf.
+type Assignment = (u32, u32);
+
+fn parse_line(line: &str) -> Result<[Assignment; 2], String> {
+    let numbers = line
+        .split(&['-', ','])
+        .map(|s| s.parse().map_err(|_| format!("invalid section ID '{s}'")))
+        .collect::<Result<Vec<u32>, _>>()?;
+    let [elf_1_start, elf_1_end, elf_2_start, elf_2_end] = numbers[..] else {
+        return Err(format!("invalid line format {line}"));
+    };
+    if elf_1_start > elf_1_end || elf_2_start > elf_2_end {
+        return Err(format!("assignment ends before it starts on line {line}"));
+    }
+    Ok([(elf_1_start, elf_1_end), (elf_2_start, elf_2_end)])
+}
+
+// Answers questions about all the assignments of the file at once, instead of pair by pair:
+// - `most-covered` gives the sections assigned to the most elves.
+// - `uncovered:A-B` gives the sections between A and B that no elf is assigned to.
+// - `overlaps` lists every pair of elves, from any lines, with overlapping assignments. Elves are
+//   named by their line and position on it, e.g. 3.2 is the second elf of the third line.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    let mut assignments = vec![];
+    for line in input.lines() {
+        assignments.extend(parse_line(line)?);
+    }
+
+    match query.split_once(':') {
+        None if query == "most-covered" => {
+            let coverage = coverage(&assignments);
+            let max_elves = coverage.iter().map(|&(_, elves)| elves).max().unwrap();
+            if max_elves == 0 {
+                return Ok("no sections are assigned".to_string());
+            }
+            let sections = sections_where(&coverage, |elves| elves == max_elves, 0, u32::MAX);
+            Ok(format!("{max_elves} elves: {sections}"))
+        }
+        Some(("uncovered", range)) => {
+            let invalid_range = || format!("invalid range '{range}'");
+            let (start, end) = range.split_once('-').ok_or_else(invalid_range)?;
+            let start = start.parse().map_err(|_| invalid_range())?;
+            let end = end.parse().map_err(|_| invalid_range())?;
+            if start > end {
+                return Err(invalid_range());
+            }
+            let coverage = coverage(&assignments);
+            Ok(sections_where(&coverage, |elves| elves == 0, start, end))
+        }
+        None if query == "overlaps" => {
+            let pairs = overlapping_pairs(&assignments);
+            let elf_name = |i: usize| format!("{}.{}", i / 2 + 1, i % 2 + 1);
+            let mut lines = vec![format!("{} overlapping pairs", pairs.len())];
+            lines.extend(
+                pairs
+                    .into_iter()
+                    .map(|(a, b)| format!("{} {}", elf_name(a), elf_name(b))),
+            );
+            Ok(lines.join("\n"))
+        }
+        _ => Err(format!(
+            "unknown query '{query}', try most-covered, uncovered:A-B or overlaps"
+        )),
+    }
+}
+
+// Sweeps the assignments from left to right, returning the section IDs where the number of elves
+// assigned changes, along with that number. The first entry is always for section 0, and after
+// the last one, no sections are assigned. Positions are u64 so the section after u32::MAX fits.
+fn coverage(assignments: &[Assignment]) -> Vec<(u64, usize)> {
+    let mut events: Vec<(u64, isize)> = vec![(0, 0)];
+    for &(start, end) in assignments {
+        events.push((start as u64, 1));
+        events.push((end as u64 + 1, -1));
+    }
+    events.sort_unstable();
+
+    let mut coverage: Vec<(u64, usize)> = vec![];
+    let mut elves = 0;
+    for (position, change) in events {
+        elves = (elves as isize + change) as usize;
+        match coverage.last_mut() {
+            Some(last) if last.0 == position => last.1 = elves,
+            _ => coverage.push((position, elves)),
+        }
+    }
+    coverage.dedup_by(|next, prev| next.1 == prev.1);
+    coverage
+}
+
+// Describes the sections between start and end whose number of elves matches a predicate, like
+// "1-4, 7, 10-12".
+fn sections_where(
+    coverage: &[(u64, usize)],
+    matches: impl Fn(usize) -> bool,
+    start: u32,
+    end: u32,
+) -> String {
+    let (start, end) = (start as u64, end as u64);
+    let mut ranges = vec![];
+    for (i, &(position, elves)) in coverage.iter().enumerate() {
+        let next_position = coverage.get(i + 1).map_or(u32::MAX as u64 + 1, |c| c.0);
+        let (range_start, range_end) = (position.max(start), (next_position - 1).min(end));
+        if matches(elves) && range_start <= range_end {
+            if range_start == range_end {
+                ranges.push(range_start.to_string());
+            } else {
+                ranges.push(format!("{range_start}-{range_end}"));
+            }
+        }
+    }
+    if ranges.is_empty() {
+        return "none".to_string();
+    }
+    ranges.join(", ")
+}
+
+// Finds the pairs of overlapping assignments, as indices into the slice, by sweeping them from left
+// to right while keeping the ones that are active. When an assignment starts, it overlaps exactly
+// the active ones, so this takes O(n log n) plus the number of pairs found.
+fn overlapping_pairs(assignments: &[Assignment]) -> Vec<(usize, usize)> {
+    // Ends sort before starts on the same position, as an assignment ending at section N doesn't
+    // overlap one starting at section N+1.
+    const END: u8 = 0;
+    const START: u8 = 1;
+    let mut events = vec![];
+    for (i, &(start, end)) in assignments.iter().enumerate() {
+        events.push((start as u64, START, i));
+        events.push((end as u64 + 1, END, i));
+    }
+    events.sort_unstable();
+
+    let mut pairs = vec![];
+    let mut active = vec![];
+    // Where each active assignment is on the active list, so they can be removed in O(1).
+    let mut active_index = vec![0; assignments.len()];
+    for (_, kind, i) in events {
+        if kind == START {
+            pairs.extend(active.iter().map(|&j| (i.min(j), i.max(j))));
+            active_index[i] = active.len();
+            active.push(i);
+        } else {
+            let index = active_index[i];
+            active.swap_remove(index);
+            if let Some(&moved) = active.get(index) {
+                active_index[moved] = index;
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+// Generates `size` pairs of section assignments.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let mut random_range = || {
+        let start = rng.range(1..=99);
+        (start, rng.range(start..=99))
+    };
+    (0..size.max(1))
+        .map(|_| {
+            let ((start_1, end_1), (start_2, end_2)) = (random_range(), random_range());
+            format!("{start_1}-{end_1},{start_2}-{end_2}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, vecs};
+
+    const SAMPLE: &str = "2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7\n6-6,4-6\n2-6,4-8\n";
+
+    #[test]
+    fn sample_queries() {
+        let answer = |q| query(SAMPLE, q);
+        assert_eq!(answer("most-covered"), Ok("8 elves: 6".to_string()));
+        assert_eq!(answer("uncovered:0-12"), Ok("0-1, 10-12".to_string()));
+        assert_eq!(answer("uncovered:3-5"), Ok("none".to_string()));
+        let overlaps = answer("overlaps").unwrap();
+        assert!(overlaps.starts_with("49 overlapping pairs\n1.1 2.1\n"));
+        assert!(!overlaps.contains("1.1 1.2"));
+        assert_eq!(
+            query("", "most-covered"),
+            Ok("no sections are assigned".to_string())
+        );
+        assert!(answer("uncovered:5").is_err());
+        assert!(answer("uncovered:5-3").is_err());
+        assert!(answer("nope").is_err());
+    }
+
+    #[test]
+    fn reversed_assignments_are_rejected() {
+        assert_eq!(parse_line("3-5,1-1"), Ok([(3, 5), (1, 1)]));
+        assert!(parse_line("5-3,1-1").is_err());
+        assert!(parse_line("1-1,5-3").is_err());
+        assert!(query("5-3,1-1\n", "most-covered").is_err());
+    }
+
+    #[test]
+    fn sweep_finds_same_overlaps_as_comparing_all_pairs() {
+        let assignments = vecs((ints(1..=30), ints(0..=10)), 0..=40);
+        check(&assignments, |assignments| {
+            let assignments: Vec<_> = assignments
+                .iter()
+                .map(|&(start, len)| (start as u32, (start + len) as u32))
+                .collect();
+            let mut expected = vec![];
+            for (i, a) in assignments.iter().enumerate() {
+                for (j, b) in assignments.iter().enumerate().skip(i + 1) {
+                    if a.1 >= b.0 && a.0 <= b.1 {
+                        expected.push((i, j));
+                    }
+                }
+            }
+            overlapping_pairs(&assignments) == expected
+        });
+    }
+}
@@ -1,75 +1,688 @@
+use crate::dijkstra::{self, Limits, Outcome};
+use crate::rng::Rng;
+use std::{fmt, fs};
+
 pub fn run(input: &str) -> String {
-    let (initial_stacks_section, crane_moves_section) = input
-        .split_once("\n\n")
-        .expect("input should have two sections separated by double newlines");
-    let initial_stacks = parse_stacks(initial_stacks_section);
-    let crane_moves = parse_moves(crane_moves_section);
+    let (initial_stacks, crane_moves) = parse_input(input);
+    let top_crate_letters = |crane: &dyn Crane| {
+        let stacks = exec_moves(&initial_stacks, &crane_moves, crane)
+            .unwrap_or_else(|err| panic!("{} can't {err}", crane.name()));
+        get_top_crate_letters(&stacks)
+    };
     format!(
         "{} {}",
-        exec_moves(&initial_stacks, &crane_moves, move_as_crate_mover_9000),
-        exec_moves(&initial_stacks, &crane_moves, move_as_crate_mover_9001)
+        top_crate_letters(&CrateMover9000),
+        top_crate_letters(&CrateMover9001)
     )
 }
 
+// Alternative entry point that shows the stacks after every move, for both crane models.
+pub fn trace(input: &str) -> String {
+    let (initial_stacks, crane_moves) = parse_input(input);
+    let cranes: [&dyn Crane; 2] = [&CrateMover9000, &CrateMover9001];
+    let mut steps = vec![];
+    for crane in cranes {
+        steps.push(format!(
+            "{}\n\n{}",
+            crane.name(),
+            render_stacks(&initial_stacks)
+        ));
+        let mut stacks = initial_stacks.clone();
+        for (move_index, crane_move) in crane_moves.iter().enumerate() {
+            if let Err(err) = exec_move(move_index, crane_move, &mut stacks, crane) {
+                steps.push(format!("Can't {err}"));
+                break;
+            }
+            steps.push(format!("{crane_move}\n\n{}", render_stacks(&stacks)));
+        }
+    }
+    steps.join("\n\n")
+}
+
+// Answers other questions about the stacks and moves, with any of the crane models: `9000`,
+// `9001`, `alternating` or `capacity:N`.
+// - `crane:CRANE` runs the moves with that crane and gives the top crates.
+// - `undo:CRANE` takes the stacks of the input as the final ones, and undoes the moves to find the
+//   initial stacks.
+// - `rearrange:CRANE:FILE` looks for the fewest moves that turn the input's stacks into the ones
+//   drawn on the file.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    let unknown_query =
+        || format!("unknown query '{query}', try crane:CRANE, undo:CRANE or rearrange:CRANE:FILE");
+    let (name, arg) = query.split_once(':').ok_or_else(unknown_query)?;
+    let (stacks, crane_moves) = parse_input(input);
+    match name {
+        "crane" => {
+            let crane = parse_crane(arg)?;
+            let stacks = exec_moves(&stacks, &crane_moves, crane.as_ref())
+                .map_err(|err| format!("{} can't {err}", crane.name()))?;
+            Ok(get_top_crate_letters(&stacks))
+        }
+        "undo" => {
+            let crane = parse_crane(arg)?;
+            let initial_stacks = undo_moves(&stacks, &crane_moves, crane.as_ref())
+                .map_err(|err| format!("{} can't un{err}", crane.name()))?;
+            Ok(format!("\n{}", render_stacks(&initial_stacks)))
+        }
+        "rearrange" => {
+            let (crane, path) = arg.rsplit_once(':').ok_or_else(unknown_query)?;
+            let crane = parse_crane(crane)?;
+            let target = fs::read_to_string(path)
+                .map_err(|err| format!("error reading target stacks {path}: {err}"))?;
+            let target = parse_stacks(target.trim_end_matches('\n'))
+                .map_err(|err| format!("invalid target stacks: {err}"))?;
+            let crane_moves = rearrange(&stacks, &target, crane.as_ref())?;
+            let mut lines = vec![format!("{} moves", crane_moves.len())];
+            lines.extend(crane_moves.iter().map(|crane_move| crane_move.to_string()));
+            Ok(lines.join("\n"))
+        }
+        _ => Err(unknown_query()),
+    }
+}
+
+fn parse_crane(name: &str) -> Result<Box<dyn Crane>, String> {
+    match name {
+        "9000" => Ok(Box::new(CrateMover9000)),
+        "9001" => Ok(Box::new(CrateMover9001)),
+        "alternating" => Ok(Box::new(AlternatingCrane)),
+        _ => {
+            let capacity = name
+                .strip_prefix("capacity:")
+                .and_then(|capacity| capacity.parse().ok())
+                .filter(|&capacity| capacity > 0);
+            match capacity {
+                Some(capacity) => Ok(Box::new(CapacityLimitedCrane { capacity })),
+                None => Err(format!(
+                    "unknown crane '{name}', try 9000, 9001, alternating or capacity:N"
+                )),
+            }
+        }
+    }
+}
+
+fn parse_input(input: &str) -> (Vec<Stack>, Vec<Move>) {
+    let (initial_stacks_section, crane_moves_section) = input
+        .split_once("\n\n")
+        .expect("input should have two sections separated by double newlines");
+    let initial_stacks = parse_stacks(initial_stacks_section)
+        .unwrap_or_else(|err| panic!("invalid initial stacks: {err}"));
+    let crane_moves =
+        parse_moves(crane_moves_section).unwrap_or_else(|err| panic!("invalid moves: {err}"));
+    (initial_stacks, crane_moves)
+}
+
+// Entry point for fuzzing the stacks parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    let (stacks_section, _) = input.split_once("\n\n").unwrap_or((input, ""));
+    let _ = parse_stacks(stacks_section);
+}
+
 type Stack = Vec<char>;
-struct Move(usize, usize, usize);
-type MoveFn = fn(&Move, &mut [Stack]) -> ();
-
-fn parse_stacks(input: &str) -> Vec<Stack> {
-    // Collect stacks in reverse order and ignore last line with stack names.
-    let lines: Vec<_> = input.lines().rev().skip(1).map(str::as_bytes).collect();
-    let mut stacks = vec![];
-    for i in 0.. {
-        let char_index = i * 4 + 1;
-        if char_index >= lines[0].len() {
-            break;
-        }
-        let stack: Stack = lines
+// A number of crates and the indices of the stacks they are moved from and to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move(usize, usize, usize);
+
+// A model of crane, which decides how the crates are arranged when moved. Moves are checked
+// before being given to the crane, so it can count on both stacks existing and on the source
+// stack having enough crates. Cranes must only take the moved crates from the top of the source
+// stack and put them, in any order, on top of the destination stack; that's what allows undoing
+// their moves. They can behave differently on different moves, as long as they repeat themselves
+// every `cycle_len` moves, and they're told the index of each move within that cycle.
+pub trait Crane {
+    fn name(&self) -> String;
+    fn move_crates(&self, cycle_index: usize, crane_move: &Move, stacks: &mut [Stack]);
+
+    fn cycle_len(&self) -> usize {
+        1
+    }
+}
+
+// Moves one crate at a time, so the moved crates end up in reverse order.
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn move_crates(&self, _: usize, &Move(crate_count, from, to): &Move, stacks: &mut [Stack]) {
+        for _ in 0..crate_count {
+            let top_crate = stacks[from].pop().unwrap();
+            stacks[to].push(top_crate);
+        }
+    }
+}
+
+// Moves all the crates at once, so they keep their order.
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn move_crates(&self, _: usize, &Move(crate_count, from, to): &Move, stacks: &mut [Stack]) {
+        let bottom_crate_index = stacks[from].len() - crate_count;
+        let moved_crates = stacks[from].split_off(bottom_crate_index);
+        stacks[to].extend(moved_crates);
+    }
+}
+
+// Can only lift a few crates at a time, so bigger moves take several lifts, each of which keeps
+// the order of its crates. A capacity of 1 works like the CrateMover 9000.
+struct CapacityLimitedCrane {
+    capacity: usize,
+}
+
+impl Crane for CapacityLimitedCrane {
+    fn name(&self) -> String {
+        format!("crane with capacity {}", self.capacity)
+    }
+
+    fn move_crates(&self, _: usize, &Move(crate_count, from, to): &Move, stacks: &mut [Stack]) {
+        let mut remaining = crate_count;
+        while remaining > 0 {
+            let lifted = remaining.min(self.capacity);
+            CrateMover9001.move_crates(0, &Move(lifted, from, to), stacks);
+            remaining -= lifted;
+        }
+    }
+}
+
+// A crane with a loose gear that works like the CrateMover 9000 on every other move, starting with
+// the first one, and like the CrateMover 9001 on the rest.
+struct AlternatingCrane;
+
+impl Crane for AlternatingCrane {
+    fn name(&self) -> String {
+        "alternating crane".to_string()
+    }
+
+    fn move_crates(&self, cycle_index: usize, crane_move: &Move, stacks: &mut [Stack]) {
+        if cycle_index == 0 {
+            CrateMover9000.move_crates(cycle_index, crane_move, stacks);
+        } else {
+            CrateMover9001.move_crates(cycle_index, crane_move, stacks);
+        }
+    }
+
+    fn cycle_len(&self) -> usize {
+        2
+    }
+}
+
+// A move that can't be executed. Moves and stacks are indices, starting from 0.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MoveError {
+    NoSuchStack {
+        move_index: usize,
+        stack: usize,
+    },
+    NotEnoughCrates {
+        move_index: usize,
+        stack: usize,
+        requested: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveError::NoSuchStack { move_index, stack } => write!(
+                f,
+                "execute move {}: there's no stack {}",
+                move_index + 1,
+                stack + 1
+            ),
+            MoveError::NotEnoughCrates {
+                move_index,
+                stack,
+                requested,
+                available,
+            } => write!(
+                f,
+                "execute move {}: {requested} crates requested from stack {}, which has {available}",
+                move_index + 1,
+                stack + 1
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Move(crate_count, from, to) = self;
+        write!(f, "move {crate_count} from {} to {}", from + 1, to + 1)
+    }
+}
+
+fn parse_stacks(input: &str) -> Result<Vec<Stack>, String> {
+    // The last line has the stack names, and the crates are stacked above it.
+    let mut lines: Vec<_> = input.lines().map(str::as_bytes).collect();
+    let names_line = lines.pop().ok_or("missing stack names line")?;
+    let stack_count = names_line
+        .split(|&ch| ch == b' ')
+        .filter(|name| !name.is_empty())
+        .count();
+
+    let mut stacks = vec![Stack::new(); stack_count];
+    for (level, line) in lines.iter().rev().enumerate() {
+        // Lines can be shorter than the names line if they had trailing spaces trimmed.
+        for (i, &ch) in line.iter().skip(1).step_by(4).enumerate() {
+            if ch == b' ' {
+                continue;
+            }
+            if !ch.is_ascii_uppercase() {
+                return Err(format!("invalid crate '{}'", ch.escape_ascii()));
+            }
+            let Some(stack) = stacks.get_mut(i) else {
+                return Err(format!("crate '{}' has no stack name below", ch as char));
+            };
+            if stack.len() != level {
+                return Err(format!(
+                    "crate '{}' is floating over stack {}",
+                    ch as char,
+                    i + 1
+                ));
+            }
+            stack.push(ch as char);
+        }
+    }
+    Ok(stacks)
+}
+
+// The inverse of `parse_stacks`: draws the stacks as crates over a line of stack names, with every
+// line padded to the full width like on the puzzle's examples.
+fn render_stacks(stacks: &[Stack]) -> String {
+    let max_height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut lines = vec![];
+    for level in (0..max_height).rev() {
+        let row: Vec<_> = stacks
             .iter()
-            .map(|line| line[char_index] as char)
-            .filter(|&ch| ch != ' ')
+            .map(|stack| match stack.get(level) {
+                Some(crate_letter) => format!("[{crate_letter}]"),
+                None => "   ".to_string(),
+            })
             .collect();
-        stacks.push(stack);
+        lines.push(row.join(" "));
     }
-    stacks
+    let stack_names: Vec<_> = (1..=stacks.len()).map(|i| format!(" {i} ")).collect();
+    lines.push(stack_names.join(" "));
+    lines.join("\n")
 }
 
-fn parse_moves(input: &str) -> Vec<Move> {
+fn parse_moves(input: &str) -> Result<Vec<Move>, String> {
     input.lines().map(parse_move).collect()
 }
 
-fn parse_move(line: &str) -> Move {
+fn parse_move(line: &str) -> Result<Move, String> {
     let nums: Vec<usize> = line
         .split(' ')
         .filter_map(|word| word.parse().ok())
         .collect();
     let [crate_count, from, to] = nums[..] else {
-        panic!("invalid line {line}")
+        return Err(format!("invalid line {line}"));
     };
-    Move(crate_count, from - 1, to - 1)
+    if from == 0 || to == 0 {
+        return Err(format!("stack numbers start from 1 on line {line}"));
+    }
+    Ok(Move(crate_count, from - 1, to - 1))
 }
 
-fn exec_moves(initial_stacks: &[Stack], crane_moves: &[Move], move_fn: MoveFn) -> String {
+fn exec_moves(
+    initial_stacks: &[Stack],
+    crane_moves: &[Move],
+    crane: &dyn Crane,
+) -> Result<Vec<Stack>, MoveError> {
     let mut stacks = initial_stacks.to_vec();
-    for crane_move in crane_moves.iter() {
-        move_fn(crane_move, &mut stacks)
+    for (move_index, crane_move) in crane_moves.iter().enumerate() {
+        exec_move(move_index, crane_move, &mut stacks, crane)?;
     }
-    get_top_crate_letters(&stacks)
+    Ok(stacks)
 }
 
-fn move_as_crate_mover_9000(&Move(crate_count, from, to): &Move, stacks: &mut [Stack]) {
-    for _ in 0..crate_count {
-        let top_crate = stacks[from].pop().expect("stack should have a crate");
-        stacks[to].push(top_crate);
+fn exec_move(
+    move_index: usize,
+    crane_move: &Move,
+    stacks: &mut [Stack],
+    crane: &dyn Crane,
+) -> Result<(), MoveError> {
+    let &Move(crate_count, from, to) = crane_move;
+    for stack in [from, to] {
+        if stack >= stacks.len() {
+            return Err(MoveError::NoSuchStack { move_index, stack });
+        }
+    }
+    if stacks[from].len() < crate_count {
+        return Err(MoveError::NotEnoughCrates {
+            move_index,
+            stack: from,
+            requested: crate_count,
+            available: stacks[from].len(),
+        });
+    }
+    crane.move_crates(move_index % crane.cycle_len(), crane_move, stacks);
+    Ok(())
+}
+
+// Finds the stacks before the moves were executed, by undoing them from last to first. As cranes
+// only shuffle the crates they move, each move is undone by seeing how the crane shuffles some
+// labeled crates, and putting the real ones back in the order the labels came from.
+fn undo_moves(
+    final_stacks: &[Stack],
+    crane_moves: &[Move],
+    crane: &dyn Crane,
+) -> Result<Vec<Stack>, MoveError> {
+    let mut stacks = final_stacks.to_vec();
+    for (move_index, crane_move) in crane_moves.iter().enumerate().rev() {
+        let &Move(crate_count, from, to) = crane_move;
+        for stack in [from, to] {
+            if stack >= stacks.len() {
+                return Err(MoveError::NoSuchStack { move_index, stack });
+            }
+        }
+        if stacks[to].len() < crate_count {
+            return Err(MoveError::NotEnoughCrates {
+                move_index,
+                stack: to,
+                requested: crate_count,
+                available: stacks[to].len(),
+            });
+        }
+
+        let labels: Stack = (0..crate_count as u32)
+            .map(|i| char::from_u32(i).expect("too many crates to label"))
+            .collect();
+        let mut labeled_stacks = vec![Stack::new(); stacks.len()];
+        labeled_stacks[from] = labels;
+        crane.move_crates(
+            move_index % crane.cycle_len(),
+            crane_move,
+            &mut labeled_stacks,
+        );
+        let labels_index = labeled_stacks[to].len() - crate_count;
+        let shuffled_labels = labeled_stacks[to].split_off(labels_index);
+
+        let moved_crates_index = stacks[to].len() - crate_count;
+        let moved_crates = stacks[to].split_off(moved_crates_index);
+        let mut original_crates = vec![' '; crate_count];
+        for (label, crate_letter) in shuffled_labels.into_iter().zip(moved_crates) {
+            original_crates[label as usize] = crate_letter;
+        }
+        stacks[from].extend(original_crates);
     }
+    Ok(stacks)
 }
 
-fn move_as_crate_mover_9001(&Move(crate_count, from, to): &Move, stacks: &mut [Stack]) {
-    let bottom_crate_index = stacks[from].len() - crate_count;
-    let moved_crates = stacks[from].split_off(bottom_crate_index);
-    stacks[to].extend(moved_crates);
+fn possible_moves(stacks: &[Stack]) -> Vec<Move> {
+    let mut moves = vec![];
+    for from in 0..stacks.len() {
+        for to in (0..stacks.len()).filter(|&to| to != from) {
+            moves.extend((1..=stacks[from].len()).map(|crate_count| Move(crate_count, from, to)));
+        }
+    }
+    moves
+}
+
+// On the real input's stacks, each state has 448 successors, as any of its 56 crates can be moved
+// along with the ones above it to any of the other 8 stacks, so expanding 2_000 states already
+// takes a few seconds. That's enough to find any rearrangement of up to 5 moves there. Longer ones
+// need many times more states, as their number grows exponentially with the moves, so a higher
+// limit would mostly make the search take longer before giving up.
+const MAX_REARRANGE_EXPANDED_STATES: usize = 2_000;
+
+// Finds the fewest moves that turn some stacks into the target ones with A*. States also keep
+// track of where the crane is on its cycle, as the same move can give different results on
+// different moves.
+fn rearrange(
+    initial_stacks: &[Stack],
+    target_stacks: &[Stack],
+    crane: &dyn Crane,
+) -> Result<Vec<Move>, String> {
+    if initial_stacks.len() != target_stacks.len() {
+        return Err("the target should have the same number of stacks".to_string());
+    }
+    let sorted_crates = |stacks: &[Stack]| {
+        let mut crates: Vec<_> = stacks.iter().flatten().copied().collect();
+        crates.sort();
+        crates
+    };
+    if sorted_crates(initial_stacks) != sorted_crates(target_stacks) {
+        return Err("the target should have the same crates".to_string());
+    }
+
+    // Each expanded state adds hundreds of new ones, so close to a million states can be kept
+    // before reaching the limit. Their stacks are kept as a single string, like "ZN|MCD|P", to save
+    // memory.
+    type State = (Box<str>, usize);
+    let encode = |stacks: &[Stack]| -> Box<str> {
+        let stacks: Vec<String> = stacks.iter().map(|stack| stack.iter().collect()).collect();
+        stacks.join("|").into()
+    };
+    let decode = |encoded: &str| -> Vec<Stack> {
+        encoded
+            .split('|')
+            .map(|stack| stack.chars().collect())
+            .collect()
+    };
+    let successors = |(encoded, cycle_index): &State| {
+        let stacks = decode(encoded);
+        let next_cycle_index = (cycle_index + 1) % crane.cycle_len();
+        possible_moves(&stacks)
+            .into_iter()
+            .map(|crane_move| {
+                let mut next_stacks = stacks.clone();
+                crane.move_crates(*cycle_index, &crane_move, &mut next_stacks);
+                ((encode(&next_stacks), next_cycle_index), 1)
+            })
+            .collect::<Vec<_>>()
+    };
+    // Stacks that have crates over the part that already matches the target need at least one
+    // move from them, and stacks that don't have all of that part need at least one move to them.
+    // As each move has one source and one destination, the largest of both counts is a lower bound
+    // of the moves left.
+    let heuristic = |(encoded, _): &State| {
+        let (mut stacks_to_move_from, mut stacks_to_move_to) = (0, 0);
+        for (stack, target) in encoded.split('|').zip(target_stacks) {
+            let matching = stack
+                .chars()
+                .zip(target)
+                .take_while(|(a, b)| a == *b)
+                .count();
+            stacks_to_move_from += (stack.len() > matching) as usize;
+            stacks_to_move_to += (target.len() > matching) as usize;
+        }
+        stacks_to_move_from.max(stacks_to_move_to)
+    };
+
+    let target = encode(target_stacks);
+    let limits = Limits {
+        max_expanded: Some(MAX_REARRANGE_EXPANDED_STATES),
+        ..Limits::default()
+    };
+    let (outcome, _) = dijkstra::astar_weighted_with_route(
+        &(encode(initial_stacks), 0),
+        |(encoded, _)| *encoded == target,
+        successors,
+        heuristic,
+        &limits,
+    );
+    let route = match outcome {
+        Outcome::Found((_, route)) => route,
+        Outcome::Unreachable => return Err(format!("{} can't reach the target", crane.name())),
+        Outcome::LimitReached => {
+            return Err(format!(
+                "no rearrangement found after exploring {MAX_REARRANGE_EXPANDED_STATES} states"
+            ))
+        }
+    };
+
+    // Find which of the possible moves leads from each state to the next one.
+    let crane_moves = route
+        .windows(2)
+        .map(|pair| {
+            successors(&pair[0])
+                .into_iter()
+                .position(|(next_state, _)| next_state == pair[1])
+                .map(|i| possible_moves(&decode(&pair[0].0))[i])
+                .unwrap()
+        })
+        .collect();
+    Ok(crane_moves)
 }
 
 fn get_top_crate_letters(stacks: &[Stack]) -> String {
     stacks.iter().filter_map(|stack| stack.last()).collect()
 }
+
+// Generates up to 9 stacks of crates and `size` crane moves that never take more crates than
+// there are on a stack.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let stack_count = rng.range(3..=9) as usize;
+    let mut stacks: Vec<Stack> = (0..stack_count)
+        .map(|_| {
+            let height = rng.range(1..=8);
+            (0..height)
+                .map(|_| (b'A' + rng.below(26) as u8) as char)
+                .collect()
+        })
+        .collect();
+
+    let mut output = render_stacks(&stacks);
+    output += "\n\n";
+
+    for _ in 0..size.max(1) {
+        let non_empty_stacks: Vec<_> = (0..stack_count)
+            .filter(|&i| !stacks[i].is_empty())
+            .collect();
+        let from = *rng.choose(&non_empty_stacks);
+        let to = (from + rng.range(1..=stack_count as i64 - 1) as usize) % stack_count;
+        let crate_count = rng.range(1..=stacks[from].len() as i64) as usize;
+        CrateMover9001.move_crates(0, &Move(crate_count, from, to), &mut stacks);
+        output += &format!("{}\n", Move(crate_count, from, to));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SAMPLE_STACKS: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ";
+
+    #[test]
+    fn crane_models() {
+        let stacks = parse_stacks(SAMPLE_STACKS).unwrap();
+        let moves = [Move(2, 0, 1), Move(4, 1, 2)];
+        let top_crates =
+            |crane: &dyn Crane| get_top_crate_letters(&exec_moves(&stacks, &moves, crane).unwrap());
+        // Stack 3 ends up as PZNDC, PCDZN, PZNCD (two lifts of 2) and PCDNZ respectively.
+        assert_eq!(top_crates(&CrateMover9000), "MC");
+        assert_eq!(top_crates(&CrateMover9001), "MN");
+        assert_eq!(top_crates(&CapacityLimitedCrane { capacity: 1 }), "MC");
+        assert_eq!(top_crates(&CapacityLimitedCrane { capacity: 4 }), "MN");
+        assert_eq!(top_crates(&CapacityLimitedCrane { capacity: 2 }), "MD");
+        assert_eq!(top_crates(&AlternatingCrane), "MZ");
+    }
+
+    #[test]
+    fn undo_reverses_moves() {
+        let cranes: [&dyn Crane; 5] = [
+            &CrateMover9000,
+            &CrateMover9001,
+            &CapacityLimitedCrane { capacity: 2 },
+            &CapacityLimitedCrane { capacity: 3 },
+            &AlternatingCrane,
+        ];
+        for seed in 0..20 {
+            let (initial_stacks, crane_moves) =
+                parse_input(&generate_input(&mut Rng::new(seed), 30));
+            for crane in cranes {
+                let final_stacks = exec_moves(&initial_stacks, &crane_moves, crane).unwrap();
+                let undone_stacks = undo_moves(&final_stacks, &crane_moves, crane).unwrap();
+                assert_eq!(
+                    undone_stacks,
+                    initial_stacks,
+                    "seed {seed}, {}",
+                    crane.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rearrange_finds_fewest_moves() {
+        let stacks = parse_stacks(SAMPLE_STACKS).unwrap();
+        let sample_moves = [Move(1, 1, 0), Move(3, 0, 2), Move(2, 1, 0), Move(1, 0, 1)];
+        let cranes: [&dyn Crane; 3] = [&CrateMover9000, &CrateMover9001, &AlternatingCrane];
+        for crane in cranes {
+            let target = exec_moves(&stacks, &sample_moves, crane).unwrap();
+            let crane_moves = rearrange(&stacks, &target, crane).unwrap();
+            assert!(crane_moves.len() <= sample_moves.len());
+            assert_eq!(exec_moves(&stacks, &crane_moves, crane).unwrap(), target);
+        }
+        // Moving the 2 top crates of stack 2 at once keeps their order.
+        let target = parse_stacks("[D]\n[C]\n[N]\n[Z] [M] [P]\n 1   2   3 ");
+        let crane_moves = rearrange(&stacks, &target.unwrap(), &CrateMover9001);
+        assert_eq!(crane_moves, Ok(vec![Move(2, 1, 0)]));
+
+        let other_crates = parse_stacks("[A] [B] [C]\n 1   2   3 ").unwrap();
+        assert!(rearrange(&stacks, &other_crates, &CrateMover9001).is_err());
+    }
+
+    #[test]
+    fn invalid_moves() {
+        let stacks = parse_stacks(SAMPLE_STACKS).unwrap();
+        let moves = [Move(1, 0, 1), Move(3, 0, 2)];
+        assert_eq!(
+            exec_moves(&stacks, &moves, &CrateMover9001).err(),
+            Some(MoveError::NotEnoughCrates {
+                move_index: 1,
+                stack: 0,
+                requested: 3,
+                available: 1
+            })
+        );
+        let moves = [Move(1, 0, 3)];
+        assert_eq!(
+            exec_moves(&stacks, &moves, &CrateMover9000).err(),
+            Some(MoveError::NoSuchStack {
+                move_index: 0,
+                stack: 3
+            })
+        );
+        assert!(parse_move("move 1 from 0 to 1").is_err());
+    }
+
+    #[test]
+    fn render_round_trips_stacks() {
+        let mut stacks_sections = vec![];
+        for entry in fs::read_dir("samples").unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("05")
+            {
+                let sample = fs::read_to_string(path).unwrap();
+                let (stacks_section, _) = sample.split_once("\n\n").unwrap();
+                stacks_sections.push(stacks_section.to_string());
+            }
+        }
+        for seed in 0..20 {
+            let input = generate_input(&mut Rng::new(seed), 5);
+            let (stacks_section, _) = input.split_once("\n\n").unwrap();
+            stacks_sections.push(stacks_section.to_string());
+        }
+
+        assert!(!stacks_sections.is_empty());
+        for stacks_section in stacks_sections {
+            let stacks = parse_stacks(&stacks_section).unwrap();
+            assert_eq!(render_stacks(&stacks), stacks_section);
+        }
+    }
+}
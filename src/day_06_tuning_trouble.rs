@@ -1,18 +1,263 @@
-use std::collections::HashSet;
+use crate::rng::Rng;
+use crate::streaming;
+use std::io::BufRead;
 
 pub fn run(input: &str) -> String {
-    let data = input.as_bytes();
-    let start_of_packet_marker =
-        find_marker(data, 4).expect("there should be a start-of-packet marker");
-    let start_of_message_marker =
-        find_marker(data, 14).expect("there should be a start-of-message marker");
-    format!("{start_of_packet_marker} {start_of_message_marker}")
-}
-
-fn find_marker(data: &[u8], distinct_chars_count: usize) -> Option<usize> {
-    (distinct_chars_count..=data.len()).find(|&i| {
-        let last_n_chars = &data[i - distinct_chars_count..i];
-        let unique_chars: HashSet<_> = last_n_chars.iter().collect();
-        unique_chars.len() == distinct_chars_count
-    })
+    run_streaming(&mut input.as_bytes())
+}
+
+// The datastream is read one byte at a time, keeping only the last few bytes in a rolling window
+// for each kind of marker, and stops as soon as both markers are found. Like `query`, it only scans
+// the first line.
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let mut start_of_packet_window = MarkerWindow::new(4);
+    let mut start_of_message_window = MarkerWindow::new(14);
+    let mut start_of_packet_marker = None;
+    let mut start_of_message_marker = None;
+
+    for (i, byte) in streaming::bytes(input).enumerate() {
+        if byte == b'\n' {
+            break;
+        }
+        if start_of_packet_window.push(byte) {
+            start_of_packet_marker.get_or_insert(i + 1);
+        }
+        if start_of_message_window.push(byte) {
+            start_of_message_marker.get_or_insert(i + 1);
+        }
+        if start_of_packet_marker.is_some() && start_of_message_marker.is_some() {
+            break;
+        }
+    }
+
+    match (start_of_packet_marker, start_of_message_marker) {
+        (Some(start_of_packet_marker), Some(start_of_message_marker)) => {
+            format!("{start_of_packet_marker} {start_of_message_marker}")
+        }
+        (None, _) => "no start-of-packet marker".to_string(),
+        (_, None) => "no start-of-message marker".to_string(),
+    }
+}
+
+// Finds all the markers of the given window sizes, e.g. `markers:4,14`, instead of only the first
+// ones. Only the first line of the input is scanned, as the datastream is a single line.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    query_streaming(&mut input.as_bytes(), query)
+}
+
+pub fn query_streaming(input: &mut dyn BufRead, query: &str) -> Result<String, String> {
+    let Some(window_sizes) = query.strip_prefix("markers:") else {
+        return Err(format!("unknown query '{query}', try markers:N,M,..."));
+    };
+    let window_sizes = window_sizes
+        .split(',')
+        .map(|size| {
+            size.parse()
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| format!("invalid window size '{size}'"))
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    let all_markers = find_all_markers(input, &window_sizes);
+    let lines: Vec<_> = window_sizes
+        .iter()
+        .zip(all_markers)
+        .map(|(window_size, markers)| {
+            if markers.is_empty() {
+                return format!("window {window_size}: no markers");
+            }
+            let marker_count: usize = markers.iter().map(|(start, end)| end - start + 1).sum();
+            let ranges: Vec<_> = markers
+                .iter()
+                .map(|&(start, end)| match start == end {
+                    true => start.to_string(),
+                    false => format!("{start}-{end}"),
+                })
+                .collect();
+            let plural = if marker_count == 1 { "" } else { "s" };
+            format!(
+                "window {window_size}: {marker_count} marker{plural} at {}",
+                ranges.join(", ")
+            )
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+// Returns the positions where each window is full of different bytes, like `run_streaming` does
+// for the first ones. Markers tend to come in runs, so they are kept as ranges of consecutive
+// positions, which also keeps the memory needed low on long datastreams.
+fn find_all_markers(input: &mut dyn BufRead, window_sizes: &[usize]) -> Vec<Vec<(usize, usize)>> {
+    // There are only 256 different bytes, so bigger windows always have repeated ones. They get no
+    // window at all, as huge sizes would take too much memory for nothing.
+    let mut windows: Vec<_> = window_sizes
+        .iter()
+        .map(|&size| (size <= 256).then(|| MarkerWindow::new(size)))
+        .collect();
+    let mut all_markers = vec![vec![]; window_sizes.len()];
+
+    for (i, byte) in streaming::bytes(input).enumerate() {
+        if byte == b'\n' {
+            break;
+        }
+        let position = i + 1;
+        let scanned_windows = windows.iter_mut().zip(all_markers.iter_mut());
+        for (window, markers) in scanned_windows.filter_map(|(w, m)| Some((w.as_mut()?, m))) {
+            if window.push(byte) {
+                match markers.last_mut() {
+                    Some((_, end)) if *end == position - 1 => *end = position,
+                    _ => markers.push((position, position)),
+                }
+            }
+        }
+    }
+    all_markers
+}
+
+// The last N bytes of the datastream, with a count of how many times each byte appears in them so
+// that checking if they are all different takes constant time.
+struct MarkerWindow {
+    bytes: Vec<u8>,
+    next_index: usize,
+    len: usize,
+    byte_counts: [usize; 256],
+    repeated_bytes: usize,
+}
+
+impl MarkerWindow {
+    fn new(size: usize) -> MarkerWindow {
+        MarkerWindow {
+            bytes: vec![0; size],
+            next_index: 0,
+            len: 0,
+            byte_counts: [0; 256],
+            repeated_bytes: 0,
+        }
+    }
+
+    // Adds a byte to the window, pushing out the oldest one if it's full, and returns whether the
+    // window is now full of different bytes.
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == self.bytes.len() {
+            let oldest = self.bytes[self.next_index] as usize;
+            self.byte_counts[oldest] -= 1;
+            if self.byte_counts[oldest] == 1 {
+                self.repeated_bytes -= 1;
+            }
+        } else {
+            self.len += 1;
+        }
+
+        self.bytes[self.next_index] = byte;
+        self.next_index = (self.next_index + 1) % self.bytes.len();
+        self.byte_counts[byte as usize] += 1;
+        if self.byte_counts[byte as usize] == 2 {
+            self.repeated_bytes += 1;
+        }
+
+        self.len == self.bytes.len() && self.repeated_bytes == 0
+    }
+}
+
+// Generates a datastream of `size` characters. Characters are picked from a few letters at a time,
+// so that markers don't come up right away, and a start-of-message marker is added somewhere if
+// none came up by chance.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let len = size.max(14);
+    let mut letters: Vec<u8> = (b'a'..=b'z').collect();
+    let mut datastream = vec![];
+    while datastream.len() < len {
+        rng.shuffle(&mut letters);
+        let alphabet = &letters[..rng.range(2..=15) as usize];
+        for _ in 0..rng.range(1..=50) {
+            datastream.push(*rng.choose(alphabet));
+        }
+    }
+    datastream.truncate(len);
+
+    let has_marker = |data: &[u8]| {
+        let mut window = MarkerWindow::new(14);
+        data.iter().any(|&byte| window.push(byte))
+    };
+    if !has_marker(&datastream) {
+        rng.shuffle(&mut letters);
+        let marker_start = rng.below(len - 13);
+        datastream[marker_start..marker_start + 14].copy_from_slice(&letters[..14]);
+    }
+    String::from_utf8(datastream).unwrap() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, strings};
+    use std::collections::HashSet;
+
+    #[test]
+    fn all_markers() {
+        let answer = |q| query("mjqjpqmgbljsphdztnvjfqwrcgsmlb\nabcd\n", q);
+        assert_eq!(
+            answer("markers:4,14,27"),
+            Ok("window 4: 24 markers at 7-30\n\
+                window 14: 7 markers at 19, 25-30\n\
+                window 27: no markers"
+                .to_string())
+        );
+        // Bytes can repeat many times within big windows.
+        let datastream = "a".repeat(1000) + "bcd";
+        assert_eq!(
+            query(&datastream, "markers:1,300"),
+            Ok("window 1: 1003 markers at 1-1003\nwindow 300: no markers".to_string())
+        );
+        // Windows can't have more different bytes than there are, but asking for them is fine.
+        assert_eq!(
+            query(&datastream, "markers:256,257,100000000000"),
+            Ok(
+                "window 256: no markers\nwindow 257: no markers\nwindow 100000000000: no markers"
+                    .to_string()
+            )
+        );
+        assert!(answer("markers:0").is_err());
+        assert!(answer("markers").is_err());
+    }
+
+    // Both only scan the first line, so markers that only appear after it are not found.
+    #[test]
+    fn markers_stop_at_the_newline() {
+        let datastream = "abcdddd\nefghijklmnopqrstuvwxyz\n";
+        assert_eq!(
+            query(datastream, "markers:4,14"),
+            Ok("window 4: 1 marker at 4\nwindow 14: no markers".to_string())
+        );
+        assert_eq!(run(datastream), "no start-of-message marker");
+        let datastream = "aaaa\nefghijklmnopqrstuvwxyz\n";
+        assert_eq!(
+            query(datastream, "markers:4,14"),
+            Ok("window 4: no markers\nwindow 14: no markers".to_string())
+        );
+        assert_eq!(run(datastream), "no start-of-packet marker");
+    }
+
+    #[test]
+    fn markers_match_checking_every_window() {
+        check(
+            &(strings("abcdef", 0..=60), ints(1..=6)),
+            |(datastream, window_size)| {
+                let window_size = *window_size as usize;
+                let positions: Vec<_> = (window_size..=datastream.len())
+                    .filter(|&i| {
+                        let window = &datastream.as_bytes()[i - window_size..i];
+                        window.iter().collect::<HashSet<_>>().len() == window_size
+                    })
+                    .collect();
+                let markers = find_all_markers(&mut datastream.as_bytes(), &[window_size]);
+                let marker_positions: Vec<_> = markers[0]
+                    .iter()
+                    .flat_map(|&(start, end)| start..=end)
+                    .collect();
+                marker_positions == positions
+            },
+        );
+    }
 }
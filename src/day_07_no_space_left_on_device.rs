@@ -1,7 +1,11 @@
+use crate::rng::Rng;
 use std::collections::HashMap;
 
 pub fn run(input: &str) -> String {
-    let dir_sizes = calc_directory_sizes_from_terminal_output(input);
+    let dir_sizes = match calc_directory_sizes_from_terminal_output(input) {
+        Ok(dir_sizes) => dir_sizes,
+        Err(err) => return format!("invalid terminal output: {err}"),
+    };
 
     let small_dirs_total_size: usize = dir_sizes
         .values()
@@ -20,36 +24,209 @@ pub fn run(input: &str) -> String {
     format!("{small_dirs_total_size} {file_to_delete_size}")
 }
 
-fn calc_directory_sizes_from_terminal_output(terminal_output: &str) -> HashMap<String, usize> {
+// Entry point for fuzzing the terminal output parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    let _ = calc_directory_sizes_from_terminal_output(input);
+}
+
+// A directory listing being read: the line of its `ls`, the path of the directory, and its entries.
+type Listing<'a> = (usize, Vec<&'a str>, Vec<&'a str>);
+
+fn calc_directory_sizes_from_terminal_output(
+    terminal_output: &str,
+) -> Result<HashMap<String, usize>, String> {
     // Keys are full directory paths, like "/foo/bar"
     let mut dir_sizes: HashMap<String, usize> = HashMap::new();
+    // Directories can be listed more than once, but their files should only be counted once. The
+    // entries of each listing are kept, sorted, to check that later listings are the same.
+    let mut listed_dirs: HashMap<String, Vec<&str>> = HashMap::new();
+    let mut curr_listing: Option<Listing> = None;
 
     let mut curr_dir_stack: Vec<&str> = vec![];
-    for line in terminal_output.lines() {
+    for (line_index, line) in terminal_output.lines().enumerate() {
+        let line_error = |message: String| format!("line {}: {message}", line_index + 1);
         let words: Vec<_> = line.split(' ').collect();
         if words[0] == "$" {
-            match words[1] {
-                "cd" => match words[2] {
-                    "/" => curr_dir_stack.clear(),
-                    ".." => {
-                        curr_dir_stack.pop().expect("dir stack should not be empty");
+            if let Some(listing) = curr_listing.take() {
+                add_listing(listing, &mut listed_dirs, &mut dir_sizes)?;
+            }
+            match words[1..] {
+                ["cd", path] => {
+                    // Like a shell, `cd` takes absolute and multi-segment paths, and going up from
+                    // the root stays there.
+                    if path.starts_with('/') {
+                        curr_dir_stack.clear();
+                    }
+                    for segment in path.split('/') {
+                        match segment {
+                            "" | "." => {}
+                            ".." => {
+                                curr_dir_stack.pop();
+                            }
+                            dir_name => curr_dir_stack.push(dir_name),
+                        }
                     }
-                    dir_name => curr_dir_stack.push(dir_name),
-                },
-                "ls" => {}
-                cmd => unreachable!("unknown command {cmd}"),
+                }
+                ["ls"] => curr_listing = Some((line_index, curr_dir_stack.clone(), vec![])),
+                _ => return Err(line_error(format!("unknown command '{line}'"))),
             }
         } else {
             // Line is part of `ls` output.
-            let dir_or_size = words[0];
-            if dir_or_size != "dir" {
-                let size: usize = dir_or_size.parse().expect("size should be a valid number");
-                for i in 0..=curr_dir_stack.len() {
-                    let dir_path = format!("/{}", curr_dir_stack[0..i].join("/"));
-                    *dir_sizes.entry(dir_path).or_insert(0) += size;
-                }
+            let Some((_, _, entries)) = &mut curr_listing else {
+                return Err(line_error(format!("unexpected line '{line}'")));
+            };
+            let [dir_or_size, _name] = words[..] else {
+                return Err(line_error(format!("invalid entry '{line}'")));
+            };
+            if dir_or_size != "dir" && dir_or_size.parse::<usize>().is_err() {
+                return Err(line_error(format!("invalid size '{dir_or_size}'")));
+            }
+            entries.push(line);
+        }
+    }
+    if let Some(listing) = curr_listing {
+        add_listing(listing, &mut listed_dirs, &mut dir_sizes)?;
+    }
+    Ok(dir_sizes)
+}
+
+// Adds the sizes of the files of a listing to its directory and all its parents, unless the
+// directory was already listed, in which case the listing must be the same as before.
+fn add_listing<'a>(
+    (ls_line_index, dir_stack, mut entries): Listing<'a>,
+    listed_dirs: &mut HashMap<String, Vec<&'a str>>,
+    dir_sizes: &mut HashMap<String, usize>,
+) -> Result<(), String> {
+    entries.sort();
+    let dir_path = format!("/{}", dir_stack.join("/"));
+    if let Some(previous_entries) = listed_dirs.get(&dir_path) {
+        if *previous_entries != entries {
+            return Err(format!(
+                "line {}: {dir_path} was listed before with different entries",
+                ls_line_index + 1
+            ));
+        }
+        return Ok(());
+    }
+
+    for entry in entries.iter() {
+        let dir_or_size = entry.split(' ').next().unwrap();
+        if dir_or_size != "dir" {
+            let size: usize = dir_or_size.parse().unwrap();
+            for i in 0..=dir_stack.len() {
+                let dir_path = format!("/{}", dir_stack[0..i].join("/"));
+                *dir_sizes.entry(dir_path).or_insert(0) += size;
             }
         }
     }
-    dir_sizes
+    listed_dirs.insert(dir_path, entries);
+    Ok(())
+}
+
+// Generates the terminal output of exploring a filesystem of `size` directories. The total used
+// space is between 40M and 70M, so that there's a need to free up space, but not too much.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    // Build a random tree with the parent of each directory, and the files on each directory.
+    let dir_count = size.max(1);
+    let parents: Vec<usize> = (0..dir_count).map(|i| rng.below(i.max(1))).collect();
+    let mut files: Vec<Vec<usize>> = (0..dir_count)
+        .map(|_| {
+            let file_count = rng.range(0..=5);
+            let max_file_size = *rng.choose(&[20_000, 300_000, 5_000_000]);
+            (0..file_count)
+                .map(|_| rng.range(1000..=max_file_size) as usize)
+                .collect()
+        })
+        .collect();
+    // Fill up the disk with a big file somewhere.
+    let used_space: usize = files.iter().flatten().sum();
+    let target_used_space = rng.range(40_000_000..=69_000_000) as usize;
+    if used_space < target_used_space {
+        files[rng.below(dir_count)].push(target_used_space - used_space);
+    }
+    while files.iter().flatten().sum::<usize>() > 70_000_000 {
+        files[rng.below(dir_count)].pop();
+    }
+
+    let mut output = String::new();
+    write_dir_listing(rng, 0, &parents, &files, &mut output);
+    output
+}
+
+fn write_dir_listing(
+    rng: &mut Rng,
+    dir: usize,
+    parents: &[usize],
+    files: &[Vec<usize>],
+    output: &mut String,
+) {
+    if dir == 0 {
+        *output += "$ cd /\n";
+    } else {
+        *output += &format!("$ cd d{dir}\n");
+    }
+    *output += "$ ls\n";
+    let subdirs: Vec<_> = (1..parents.len()).filter(|&d| parents[d] == dir).collect();
+    let mut entries: Vec<_> = subdirs.iter().map(|d| format!("dir d{d}")).collect();
+    for (i, file_size) in files[dir].iter().enumerate() {
+        let extension = *rng.choose(&["", ".txt", ".dat", ".log"]);
+        entries.push(format!("{file_size} f{i}{extension}"));
+    }
+    rng.shuffle(&mut entries);
+    for entry in entries {
+        *output += &entry;
+        output.push('\n');
+    }
+    for subdir in subdirs {
+        write_dir_listing(rng, subdir, parents, files, output);
+        *output += "$ cd ..\n";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relisted_dirs_are_counted_once() {
+        let relisted =
+            "$ cd /\n$ ls\n100 b.txt\ndir a\n$ cd a\n$ ls\n584 i\n$ cd ..\n$ ls\ndir a\n100 b.txt\n";
+        let dir_sizes = calc_directory_sizes_from_terminal_output(relisted).unwrap();
+        assert_eq!(dir_sizes["/"], 684);
+        assert_eq!(dir_sizes["/a"], 584);
+    }
+
+    #[test]
+    fn cd_takes_paths() {
+        let session = "$ cd /\n$ ls\ndir a\n$ cd a/e\n$ ls\n584 i\n$ cd ../../a/./e/..\n$ ls\ndir e\n29116 f\n$ cd ../../..\n$ ls\ndir a\n";
+        let dir_sizes = calc_directory_sizes_from_terminal_output(session).unwrap();
+        assert_eq!(dir_sizes["/"], 584 + 29116);
+        assert_eq!(dir_sizes["/a"], 584 + 29116);
+        assert_eq!(dir_sizes["/a/e"], 584);
+    }
+
+    #[test]
+    fn relistings_must_match() {
+        let error = |session| calc_directory_sizes_from_terminal_output(session).err();
+        assert_eq!(
+            error("$ ls\n100 a\n$ cd /\n$ ls\n100 a\n200 b"),
+            Some("line 4: / was listed before with different entries".to_string())
+        );
+        assert_eq!(
+            error("$ ls\n100 a\n200 b\n$ ls\n200 b"),
+            Some("line 4: / was listed before with different entries".to_string())
+        );
+        assert_eq!(
+            error("$ rm -rf /"),
+            Some("line 1: unknown command '$ rm -rf /'".to_string())
+        );
+        assert_eq!(
+            error("100 a"),
+            Some("line 1: unexpected line '100 a'".to_string())
+        );
+        assert_eq!(
+            error("$ ls\nbig a"),
+            Some("line 2: invalid size 'big'".to_string())
+        );
+    }
 }
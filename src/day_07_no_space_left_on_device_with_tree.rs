@@ -1,10 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // Another attempt at day 7, trying to use a "more reified" data structure for the file system,
 // where directories and files are represented using a tree structure.
 #[allow(dead_code)]
 pub fn run(input: &str) -> String {
-    let root = parse_fs_from_terminal_output(input);
+    let root = match parse_fs_from_terminal_output(input) {
+        Ok(root) => root,
+        Err(err) => return format!("invalid terminal output: {err}"),
+    };
 
     let unused_space = 70_000_000 - root.size();
     let space_to_free_up = 30_000_000 - unused_space;
@@ -27,16 +30,58 @@ pub fn run(input: &str) -> String {
     format!("{small_dirs_total_size} {file_to_delete_size}")
 }
 
+// Answers questions about the reconstructed filesystem:
+// - `tree` draws it like the puzzle's description does.
+// - `incomplete` lists the directories that were never listed, so their sizes might be too small.
+pub fn query(input: &str, query: &str) -> Result<String, String> {
+    let root = parse_fs_from_terminal_output(input)?;
+    match query {
+        "tree" => {
+            let mut lines = vec![];
+            root.draw("/", 0, &mut lines);
+            Ok(lines.join("\n"))
+        }
+        "incomplete" => {
+            let mut paths = vec![];
+            root.find_unlisted_dirs("", &mut paths);
+            if paths.is_empty() {
+                return Ok("none".to_string());
+            }
+            Ok(paths.join("\n"))
+        }
+        _ => Err(format!("unknown query '{query}', try tree or incomplete")),
+    }
+}
+
+// Entry point for fuzzing the filesystem parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    let _ = parse_fs_from_terminal_output(input);
+}
+
 enum FsNode {
-    File { size: usize },
-    Dir { children: HashMap<String, FsNode> },
+    File {
+        size: usize,
+    },
+    // Directories can be known to exist without ever being listed, e.g. from a parent's listing or
+    // from a `cd` into them, in which case they may have more contents than we know of.
+    Dir {
+        children: BTreeMap<String, FsNode>,
+        listed: bool,
+    },
 }
 
 impl FsNode {
+    fn new_dir() -> FsNode {
+        FsNode::Dir {
+            children: BTreeMap::new(),
+            listed: false,
+        }
+    }
+
     fn size(&self) -> usize {
         match self {
             FsNode::File { size } => *size,
-            FsNode::Dir { children } => children.values().map(FsNode::size).sum(),
+            FsNode::Dir { children, .. } => children.values().map(FsNode::size).sum(),
         }
     }
 
@@ -45,60 +90,320 @@ impl FsNode {
         F: FnMut(&FsNode),
     {
         walk_fn(self);
-        if let FsNode::Dir { children } = self {
+        if let FsNode::Dir { children, .. } = self {
             for child in children.values() {
                 child.walk(walk_fn);
             }
         }
     }
+
+    fn draw(&self, name: &str, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        match self {
+            FsNode::File { size } => lines.push(format!("{indent}- {name} (file, size={size})")),
+            FsNode::Dir { children, listed } => {
+                let not_listed = if *listed { "" } else { ", not listed" };
+                lines.push(format!("{indent}- {name} (dir{not_listed})"));
+                for (child_name, child) in children {
+                    child.draw(child_name, depth + 1, lines);
+                }
+            }
+        }
+    }
+
+    fn find_unlisted_dirs(&self, path: &str, paths: &mut Vec<String>) {
+        if let FsNode::Dir { children, listed } = self {
+            if !listed {
+                paths.push(if path.is_empty() { "/" } else { path }.to_string());
+            }
+            for (name, child) in children {
+                child.find_unlisted_dirs(&format!("{path}/{name}"), paths);
+            }
+        }
+    }
+
+    // Finds the directory at a path, creating any missing directories along the way, as a `cd`
+    // into them means that they exist.
+    fn dir_at(
+        &mut self,
+        path: &[String],
+    ) -> Result<(&mut BTreeMap<String, FsNode>, &mut bool), String> {
+        let mut node = self;
+        for (i, name) in path.iter().enumerate() {
+            let FsNode::Dir { children, .. } = node else {
+                unreachable!("only directories are walked into");
+            };
+            node = children.entry(name.clone()).or_insert_with(FsNode::new_dir);
+            if let FsNode::File { .. } = node {
+                return Err(format!(
+                    "/{} is a file, not a directory",
+                    path[..=i].join("/")
+                ));
+            }
+        }
+        let FsNode::Dir { children, listed } = node else {
+            unreachable!("only directories are walked into");
+        };
+        Ok((children, listed))
+    }
+}
+
+// A directory listing being read.
+struct Listing {
+    ls_line_index: usize,
+    // The names on the directory's earlier listing, if it was listed before.
+    previous_names: Option<BTreeSet<String>>,
+    names: BTreeSet<String>,
 }
 
-fn parse_fs_from_terminal_output(terminal_output: &str) -> FsNode {
-    let mut root_children = HashMap::new();
+// Rebuilds the filesystem from a shell session. Besides the puzzle's commands, `cd` can take
+// absolute and multi-segment paths, like `cd /a/b` or `cd ../c`, and directories can be listed more
+// than once. Listings that contradict what was known, like a file with two different sizes, or a
+// listing that leaves out something that is known to be in the directory, are reported as errors.
+fn parse_fs_from_terminal_output(terminal_output: &str) -> Result<FsNode, String> {
+    let mut root = FsNode::new_dir();
+    let mut curr_path: Vec<String> = vec![];
+    let mut listing: Option<Listing> = None;
 
-    let mut curr_dir_stack: Vec<&str> = vec![];
-    for line in terminal_output.lines() {
+    for (line_index, line) in terminal_output.lines().enumerate() {
+        let line_error = |message: String| format!("line {}: {message}", line_index + 1);
         let words: Vec<_> = line.split(' ').collect();
         if words[0] == "$" {
-            match words[1] {
-                "cd" => match words[2] {
-                    "/" => curr_dir_stack.clear(),
-                    ".." => {
-                        curr_dir_stack.pop().expect("dir stack should not be empty");
-                    }
-                    dir_name => curr_dir_stack.push(dir_name),
-                },
-                "ls" => {}
-                cmd => unreachable!("unknown command {cmd}"),
+            if let Some(listing) = listing.take() {
+                check_listing(&mut root, &curr_path, listing)?;
+            }
+        }
+        match words[..] {
+            ["$", "cd", path] => {
+                curr_path = resolve_path(&curr_path, path);
+                root.dir_at(&curr_path).map_err(line_error)?;
+            }
+            ["$", "ls"] => {
+                let (children, listed) = root.dir_at(&curr_path).map_err(line_error)?;
+                listing = Some(Listing {
+                    ls_line_index: line_index,
+                    previous_names: listed.then(|| children.keys().cloned().collect()),
+                    names: BTreeSet::new(),
+                });
+                *listed = true;
             }
-        } else {
-            // Line is part of `ls` output.
-            let dir_or_size = words[0];
-            let name = words[1].to_string();
-            let new_node = if dir_or_size == "dir" {
-                FsNode::Dir {
-                    children: HashMap::new(),
+            ["$", ..] => return Err(line_error(format!("unknown command '{line}'"))),
+            [dir_or_size, name] if listing.is_some() => {
+                if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+                    return Err(line_error(format!("invalid name '{name}'")));
                 }
-            } else {
-                let size: usize = dir_or_size.parse().expect("size should be a valid number");
-                FsNode::File { size }
-            };
-
-            let mut curr_dir_children = &mut root_children;
-            for &name in curr_dir_stack.iter() {
-                let child = curr_dir_children.get_mut(name);
-                let Some(FsNode::Dir {
-                    children: child_dir_children,
-                }) = child
-                else {
-                    panic!("curr_dir should have child directory {name}");
+                let new_node = if dir_or_size == "dir" {
+                    FsNode::new_dir()
+                } else {
+                    let size = dir_or_size
+                        .parse()
+                        .map_err(|_| line_error(format!("invalid size '{dir_or_size}'")))?;
+                    FsNode::File { size }
                 };
-                curr_dir_children = child_dir_children;
+                if let Some(listing) = &mut listing {
+                    listing.names.insert(name.to_string());
+                }
+                let (children, _) = root.dir_at(&curr_path).map_err(line_error)?;
+                let path = format!(
+                    "{}/{name}",
+                    curr_path
+                        .iter()
+                        .map(|n| format!("/{n}"))
+                        .collect::<String>()
+                );
+                match (children.get(name), new_node) {
+                    (None, new_node) => {
+                        children.insert(name.to_string(), new_node);
+                    }
+                    (Some(FsNode::Dir { .. }), FsNode::Dir { .. }) => {}
+                    (Some(&FsNode::File { size }), FsNode::File { size: new_size }) => {
+                        if size != new_size {
+                            return Err(line_error(format!(
+                                "{path} was listed with sizes {size} and {new_size}"
+                            )));
+                        }
+                    }
+                    _ => {
+                        return Err(line_error(format!(
+                            "{path} was listed both as a file and as a directory"
+                        )))
+                    }
+                }
             }
-            curr_dir_children.insert(name, new_node);
+            _ => return Err(line_error(format!("unexpected line '{line}'"))),
         }
     }
-    FsNode::Dir {
-        children: root_children,
+    if let Some(listing) = listing {
+        check_listing(&mut root, &curr_path, listing)?;
+    }
+    Ok(root)
+}
+
+// Checks that a finished listing has everything known to be in its directory, and, if the directory
+// was listed before, nothing that wasn't on the earlier listing.
+fn check_listing(root: &mut FsNode, dir_path: &[String], listing: Listing) -> Result<(), String> {
+    let listing_error = |message: String| format!("line {}: {message}", listing.ls_line_index + 1);
+    let dir = dir_path.iter().map(|n| format!("/{n}")).collect::<String>();
+    let dir_name = if dir.is_empty() { "/" } else { &dir };
+    let (children, _) = root.dir_at(dir_path).map_err(listing_error)?;
+    if let Some(missing) = children.keys().find(|name| !listing.names.contains(*name)) {
+        return Err(listing_error(format!(
+            "the listing of {dir_name} is missing {dir}/{missing}"
+        )));
+    }
+    if let Some(previous_names) = &listing.previous_names {
+        if let Some(new) = listing.names.difference(previous_names).next() {
+            return Err(listing_error(format!(
+                "{dir}/{new} wasn't on an earlier listing of {dir_name}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+// The path `cd` goes to from the current one. Going up from the root stays there, like in a shell.
+fn resolve_path(curr_path: &[String], path: &str) -> Vec<String> {
+    let mut resolved = if path.starts_with('/') {
+        vec![]
+    } else {
+        curr_path.to_vec()
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                resolved.pop();
+            }
+            name => resolved.push(name.to_string()),
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_07_no_space_left_on_device;
+    use crate::property::{check, ints, vecs};
+
+    // The terminal output of exploring a filesystem given as a list of directories, each with a
+    // number to choose its parent among the previous ones, and the sizes of its files. The root
+    // directory gets a big file so that there's always some directory worth deleting.
+    fn terminal_output(dirs: &[(i64, Vec<i64>)]) -> String {
+        let parents: Vec<usize> = (0..dirs.len())
+            .map(|i| dirs[i].0 as usize % (i + 1))
+            .collect();
+        let used_space: i64 = dirs.iter().flat_map(|(_, files)| files).sum();
+        let mut output = format!("$ cd /\n$ ls\n{} filler\n", 50_000_000 - used_space);
+        write_dir_listing(0, dirs, &parents, &mut output);
+        output
+    }
+
+    // Directory number i + 1 is the one on `dirs[i]`, and 0 is the root.
+    fn write_dir_listing(
+        dir: usize,
+        dirs: &[(i64, Vec<i64>)],
+        parents: &[usize],
+        output: &mut String,
+    ) {
+        let subdirs: Vec<_> = (1..=dirs.len())
+            .filter(|&d| parents[d - 1] == dir)
+            .collect();
+        for subdir in subdirs.iter() {
+            *output += &format!("dir d{subdir}\n");
+        }
+        for subdir in subdirs {
+            *output += &format!("$ cd d{subdir}\n$ ls\n");
+            for (i, size) in dirs[subdir - 1].1.iter().enumerate() {
+                *output += &format!("{size} f{i}\n");
+            }
+            write_dir_listing(subdir, dirs, parents, output);
+            *output += "$ cd ..\n";
+        }
+    }
+
+    const SESSION: &str = "\
+$ cd /
+$ ls
+dir a
+100 b.txt
+$ cd a/e
+$ ls
+584 i
+$ cd /a
+$ ls
+dir e
+29116 f
+$ cd ./e/..
+$ ls
+29116 f
+dir e
+$ cd /d
+";
+
+    #[test]
+    fn reconstructs_session() {
+        assert_eq!(
+            query(SESSION, "tree"),
+            Ok("- / (dir)\n\
+                \x20 - a (dir)\n\
+                \x20   - e (dir)\n\
+                \x20     - i (file, size=584)\n\
+                \x20   - f (file, size=29116)\n\
+                \x20 - b.txt (file, size=100)\n\
+                \x20 - d (dir, not listed)"
+                .to_string())
+        );
+        assert_eq!(query(SESSION, "incomplete"), Ok("/d".to_string()));
+        // Listing a directory twice doesn't count its files twice.
+        let root = parse_fs_from_terminal_output(SESSION).unwrap();
+        assert_eq!(root.size(), 100 + 584 + 29116);
+        let relisted =
+            "$ cd /\n$ ls\n100 b.txt\ndir a\n$ cd a\n$ ls\n584 i\n$ cd ..\n$ ls\ndir a\n100 b.txt\n";
+        assert_eq!(parse_fs_from_terminal_output(relisted).unwrap().size(), 684);
+    }
+
+    #[test]
+    fn conflicting_listings() {
+        let error = |session: &str| parse_fs_from_terminal_output(session).err().unwrap();
+        assert_eq!(
+            error("$ ls\n100 a\n$ ls\n200 a"),
+            "line 4: /a was listed with sizes 100 and 200"
+        );
+        assert_eq!(
+            error("$ ls\n100 a\n$ cd a"),
+            "line 3: /a is a file, not a directory"
+        );
+        assert_eq!(
+            error("$ cd /x/y\n$ cd /x\n$ ls\n5 y"),
+            "line 4: /x/y was listed both as a file and as a directory"
+        );
+        assert_eq!(
+            error("$ ls\n100 a\n200 b\n$ ls\n200 b"),
+            "line 4: the listing of / is missing /a"
+        );
+        assert_eq!(
+            error("$ cd /a/b\n$ cd /a\n$ ls\n5 c\n$ cd b"),
+            "line 3: the listing of /a is missing /a/b"
+        );
+        assert_eq!(
+            error("$ ls\n100 a\n$ ls\n100 a\n200 b"),
+            "line 3: /b wasn't on an earlier listing of /"
+        );
+        assert_eq!(error("$ rm -rf /"), "line 1: unknown command '$ rm -rf /'");
+        assert_eq!(error("100 a"), "line 1: unexpected line '100 a'");
+        assert_eq!(
+            run("$ cd /\n$ ls\n1 a\n$ ls\n"),
+            "invalid terminal output: line 4: the listing of / is missing /a"
+        );
+    }
+
+    #[test]
+    fn both_implementations_agree() {
+        let dirs = vecs((ints(0..=1000), vecs(ints(1..=100_000), 0..=4)), 0..=15);
+        check(&dirs, |dirs| {
+            let input = terminal_output(dirs);
+            run(&input) == day_07_no_space_left_on_device::run(&input)
+        });
     }
 }
@@ -1,3 +1,5 @@
+use crate::rng::Rng;
+
 pub fn run(input: &str) -> String {
     let grid: Vec<Vec<u8>> = input
         .lines()
@@ -39,3 +41,16 @@ pub fn run(input: &str) -> String {
 
     format!("{visible_trees} {best_scenic_score}")
 }
+
+// Generates a `size` x `size` map of tree heights.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let size = size.max(1);
+    (0..size)
+        .map(|_| {
+            let row: String = (0..size)
+                .map(|_| (b'0' + rng.below(10) as u8) as char)
+                .collect();
+            row + "\n"
+        })
+        .collect()
+}
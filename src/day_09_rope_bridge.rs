@@ -1,15 +1,53 @@
+use crate::rng::Rng;
+use crate::streaming;
 use std::collections::HashSet;
+use std::io::BufRead;
 use std::iter::repeat;
 
 pub fn run(input: &str) -> String {
-    format!("{} {}", run_rope_sym(2, input), run_rope_sym(10, input))
+    run_streaming(&mut input.as_bytes())
 }
 
-fn run_rope_sym(rope_length: usize, input: &str) -> usize {
-    let mut rope = vec![(0i32, 0i32); rope_length];
-    let mut visited_positions = HashSet::from([(0, 0)]);
+// Both ropes are simulated at the same time, so the moves can be read only once. Memory use only
+// depends on the number of visited positions, not on the length of the input.
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let mut short_rope = RopeSim::new(2);
+    let mut long_rope = RopeSim::new(10);
+    for line in streaming::lines(input) {
+        for direction in parse_step_movements(&line).unwrap_or_else(|err| panic!("{err}")) {
+            short_rope.step(direction);
+            long_rope.step(direction);
+        }
+    }
+    format!(
+        "{} {}",
+        short_rope.visited_positions.len(),
+        long_rope.visited_positions.len()
+    )
+}
+
+// Entry point for fuzzing the motions parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    for line in input.lines() {
+        let _ = parse_step_movements(line);
+    }
+}
+
+struct RopeSim {
+    rope: Vec<(i32, i32)>,
+    visited_positions: HashSet<(i32, i32)>,
+}
+
+impl RopeSim {
+    fn new(rope_length: usize) -> RopeSim {
+        RopeSim {
+            rope: vec![(0, 0); rope_length],
+            visited_positions: HashSet::from([(0, 0)]),
+        }
+    }
 
-    for direction in parse_step_movements(input) {
+    fn step(&mut self, direction: &str) {
+        let rope = &mut self.rope;
         let (head_x, head_y) = &mut rope[0];
         match direction {
             "L" => *head_x -= 1,
@@ -34,16 +72,33 @@ fn run_rope_sym(rope_length: usize, input: &str) -> usize {
         }
 
         let tail = rope[rope.len() - 1];
-        visited_positions.insert(tail);
+        self.visited_positions.insert(tail);
+    }
+}
+
+// Flattens a move instruction like "L 3" into 3 "L"s.
+fn parse_step_movements(line: &str) -> Result<impl Iterator<Item = &str>, String> {
+    let (direction, step_count) = line
+        .split_once(' ')
+        .ok_or_else(|| format!("invalid line '{line}'"))?;
+    if !["L", "R", "U", "D"].contains(&direction) {
+        return Err(format!("invalid direction '{direction}'"));
     }
-    visited_positions.len()
+    let step_count: usize = step_count
+        .parse()
+        .map_err(|_| format!("invalid step count '{step_count}'"))?;
+    Ok(repeat(direction).take(step_count))
 }
 
-// Flattens moves instructions like "L 3" into 3 "L"s.
-fn parse_step_movements(input: &str) -> impl Iterator<Item = &str> {
-    input.lines().flat_map(|line| {
-        let (direction, step_count) = line.split_once(' ').expect("invalid line");
-        let step_count: usize = step_count.parse().expect("invalid number");
-        repeat(direction).take(step_count)
-    })
+// Generates `size` head motions.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            format!(
+                "{} {}\n",
+                rng.choose(&["L", "R", "U", "D"]),
+                rng.range(1..=20)
+            )
+        })
+        .collect()
 }
@@ -1,38 +1,57 @@
+use crate::rng::Rng;
+use crate::streaming;
+use std::io::BufRead;
+
 pub fn run(input: &str) -> String {
+    run_streaming(&mut input.as_bytes())
+}
+
+// The signal strengths and the CRT pixels are calculated as the cycles go by, so the X values don't
+// need to be stored.
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
     let mut x = 1;
-    let mut x_values_by_cycle = vec![];
+    let mut cycle = 0;
+    let mut signal_strengths_sum = 0;
+    let mut crt_image = String::new();
+    let mut tick = |x: i32| {
+        cycle += 1;
+        if [20, 60, 100, 140, 180, 220].contains(&cycle) {
+            signal_strengths_sum += cycle * x;
+        }
+        if cycle <= 240 {
+            let crt_x = (cycle - 1) % 40;
+            let in_sprite = (x - crt_x).abs() <= 1;
+            crt_image.push(if in_sprite { '#' } else { '.' });
+            if crt_x == 39 {
+                crt_image.push('\n');
+            }
+        }
+    };
 
-    for ins in input.lines().map(parse_instruction) {
+    let instructions = streaming::lines(input)
+        .map(|line| parse_instruction(&line).unwrap_or_else(|err| panic!("{err}")));
+    for ins in instructions {
         match ins {
-            Noop => x_values_by_cycle.push(x),
+            Noop => tick(x),
             Addx(arg) => {
-                x_values_by_cycle.push(x);
-                x_values_by_cycle.push(x);
+                tick(x);
+                tick(x);
                 x += arg
             }
         }
     }
-
-    let signal_strengths_sum: i32 = [20, 60, 100, 140, 180, 220]
-        .into_iter()
-        .map(|cycle| cycle as i32 * x_values_by_cycle[cycle - 1])
-        .sum();
-
-    let mut crt_image = String::new();
-    for y in 0..6 {
-        for x in 0..40 {
-            let sprite_x = x_values_by_cycle[y * 40 + x];
-            let in_sprite = (sprite_x - x as i32).abs() <= 1;
-            let pixel = if in_sprite { '#' } else { '.' };
-            crt_image.push(pixel);
-        }
-        crt_image.push('\n');
-    }
     let crt_image = crt_image.trim_end();
 
     format!("{signal_strengths_sum}\n{crt_image}")
 }
 
+// Entry point for fuzzing the instructions parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    for line in input.lines() {
+        let _ = parse_instruction(line);
+    }
+}
+
 enum Instruction {
     Noop,
     Addx(i32),
@@ -40,11 +59,36 @@ enum Instruction {
 
 use Instruction::*;
 
-fn parse_instruction(s: &str) -> Instruction {
+fn parse_instruction(s: &str) -> Result<Instruction, String> {
     let words: Vec<_> = s.split(' ').collect();
-    match words[0] {
-        "noop" => Noop,
-        "addx" => Addx(words[1].parse().expect("addx arg must be a valid number")),
-        ins => unreachable!("invalid instruction {ins}"),
+    match words[..] {
+        ["noop"] => Ok(Noop),
+        ["addx", arg] => arg
+            .parse()
+            .map(Addx)
+            .map_err(|_| format!("addx arg must be a valid number, got '{arg}'")),
+        _ => Err(format!("invalid instruction '{s}'")),
+    }
+}
+
+// Generates a program that runs for at least 240 cycles, the ones the CRT draws. `size` is
+// ignored, as the program length is fixed by the CRT.
+pub fn generate_input(rng: &mut Rng, _size: usize) -> String {
+    let mut output = String::new();
+    let mut cycles = 0;
+    let mut x = 1;
+    while cycles < 240 {
+        if rng.chance(0.3) {
+            output += "noop\n";
+            cycles += 1;
+        } else {
+            // Keep the sprite on the screen most of the time, so the image looks like something.
+            let arg = rng.range(-20..=20).clamp(-x - 1, 40 - x);
+            let arg = if arg == 0 { 1 } else { arg };
+            output += &format!("addx {arg}\n");
+            x += arg;
+            cycles += 2;
+        }
     }
+    output
 }
@@ -1,3 +1,4 @@
+use crate::rng::Rng;
 use std::collections::{BinaryHeap, HashMap, VecDeque};
 
 pub fn run(input: &str) -> String {
@@ -8,12 +9,22 @@ pub fn run(input: &str) -> String {
     )
 }
 
+// Entry point for fuzzing the monkeys parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    for monkey_section in input.split("\n\n") {
+        let _ = Monkey::parse(monkey_section);
+    }
+}
+
 fn run_monkey_in_the_middle_rounds(
     input: &str,
     rounds_count: usize,
     relief_after_inspection: bool,
 ) -> u64 {
-    let mut monkeys: Vec<_> = input.split("\n\n").map(Monkey::parse).collect();
+    let mut monkeys: Vec<_> = input
+        .split("\n\n")
+        .map(|s| Monkey::parse(s).unwrap_or_else(|err| panic!("invalid monkey: {err}")))
+        .collect();
     // Trick: keep track of the product of all divisibility tests' divisors so that each time the
     // worry level for an item is increased we can mod that number with this and keep it from
     // ballooning out of control.
@@ -63,36 +74,47 @@ enum Operator {
 }
 
 impl Monkey {
-    fn parse(input: &str) -> Monkey {
-        let data: HashMap<_, _> = input
-            .lines()
-            .skip(1)
-            .map(|line| line.trim_start().split_once(": ").expect("invalid line"))
-            .collect();
-
-        let items: VecDeque<u64> = data["Starting items"]
+    fn parse(input: &str) -> Result<Monkey, String> {
+        let mut data = HashMap::new();
+        for line in input.lines().skip(1) {
+            let (key, value) = line
+                .trim_start()
+                .split_once(": ")
+                .ok_or_else(|| format!("invalid line '{line}'"))?;
+            data.insert(key, value);
+        }
+        let field = |name| {
+            data.get(name)
+                .copied()
+                .ok_or_else(|| format!("missing '{name}' line"))
+        };
+
+        let items = field("Starting items")?
             .split(", ")
-            .map(|s| s.parse().expect("item should be a valid number"))
-            .collect();
+            .map(|s| s.parse().map_err(|_| format!("invalid item '{s}'")))
+            .collect::<Result<VecDeque<u64>, _>>()?;
 
-        let operation_words: Vec<_> = data["Operation"].split(' ').collect();
+        let operation_words: Vec<_> = field("Operation")?.split(' ').collect();
         let operation = match operation_words[..] {
-            ["new", "=", lhs, op, rhs] if op == "+" || op == "*" => Operation::parse(lhs, rhs, op),
-            _ => unreachable!("invalid operation {}", data["Operation"]),
+            ["new", "=", lhs, op, rhs] => Operation::parse(lhs, rhs, op)?,
+            _ => return Err(format!("invalid operation '{}'", field("Operation")?)),
         };
 
-        let div_test_divisor = parse_last_number(data["Test"]) as u64;
-        let if_true_receiver = parse_last_number(data["If true"]);
-        let if_false_receiver = parse_last_number(data["If false"]);
+        let div_test_divisor = parse_last_number(field("Test")?)? as u64;
+        if div_test_divisor == 0 {
+            return Err("test divisor should not be 0".to_string());
+        }
+        let if_true_receiver = parse_last_number(field("If true")?)?;
+        let if_false_receiver = parse_last_number(field("If false")?)?;
 
-        Monkey {
+        Ok(Monkey {
             items,
             operation,
             div_test_divisor,
             if_true_receiver,
             if_false_receiver,
             inspections_count: 0,
-        }
+        })
     }
 
     fn inspect_and_throw_item(
@@ -116,12 +138,12 @@ impl Monkey {
 }
 
 impl Operation {
-    fn parse(lhs: &str, rhs: &str, operator: &str) -> Operation {
-        Operation {
-            lhs: Operand::parse(lhs),
-            rhs: Operand::parse(rhs),
-            operator: Operator::parse(operator),
-        }
+    fn parse(lhs: &str, rhs: &str, operator: &str) -> Result<Operation, String> {
+        Ok(Operation {
+            lhs: Operand::parse(lhs)?,
+            rhs: Operand::parse(rhs)?,
+            operator: Operator::parse(operator)?,
+        })
     }
 
     fn call(&self, old: u64) -> u64 {
@@ -132,12 +154,12 @@ impl Operation {
 }
 
 impl Operand {
-    fn parse(s: &str) -> Operand {
+    fn parse(s: &str) -> Result<Operand, String> {
         if s == "old" {
-            Operand::Old
+            Ok(Operand::Old)
         } else {
-            let num: u64 = s.parse().expect("invalid number");
-            Operand::Num(num)
+            let num: u64 = s.parse().map_err(|_| format!("invalid operand '{s}'"))?;
+            Ok(Operand::Num(num))
         }
     }
 
@@ -150,11 +172,11 @@ impl Operand {
 }
 
 impl Operator {
-    fn parse(operator: &str) -> Operator {
+    fn parse(operator: &str) -> Result<Operator, String> {
         match operator {
-            "+" => Operator::Add,
-            "*" => Operator::Mult,
-            _ => unreachable!("invalid operator {operator}"),
+            "+" => Ok(Operator::Add),
+            "*" => Ok(Operator::Mult),
+            _ => Err(format!("invalid operator '{operator}'")),
         }
     }
 
@@ -166,10 +188,40 @@ impl Operator {
     }
 }
 
-fn parse_last_number(s: &str) -> usize {
-    s.split(' ')
-        .last()
-        .expect("string should have at least one word")
+fn parse_last_number(s: &str) -> Result<usize, String> {
+    let last_word = s.split(' ').last().unwrap_or_default();
+    last_word
         .parse()
-        .expect("invalid number")
+        .map_err(|_| format!("invalid number '{last_word}'"))
+}
+
+// Generates `size` monkeys, between 2 and 9. Each monkey tests for divisibility by a different
+// prime, so the trick of keeping worry levels modulo the divisors' product still works.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let monkey_count = size.clamp(2, 9);
+    let mut primes = [2, 3, 5, 7, 11, 13, 17, 19, 23];
+    rng.shuffle(&mut primes);
+    let monkeys: Vec<_> = (0..monkey_count)
+        .map(|i| {
+            let items: Vec<_> = (0..rng.range(1..=6))
+                .map(|_| rng.range(50..=99).to_string())
+                .collect();
+            let operation = match rng.below(5) {
+                0 => "old * old".to_string(),
+                1 | 2 => format!("old * {}", rng.range(2..=19)),
+                _ => format!("old + {}", rng.range(1..=8)),
+            };
+            let mut other_monkey =
+                || (i + rng.range(1..=monkey_count as i64 - 1) as usize) % monkey_count;
+            let (if_true_receiver, if_false_receiver) = (other_monkey(), other_monkey());
+            format!(
+                "Monkey {i}:\n  Starting items: {}\n  Operation: new = {operation}\n  \
+                Test: divisible by {}\n    If true: throw to monkey {if_true_receiver}\n    \
+                If false: throw to monkey {if_false_receiver}\n",
+                items.join(", "),
+                primes[i]
+            )
+        })
+        .collect();
+    monkeys.join("\n")
 }
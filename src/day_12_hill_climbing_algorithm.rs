@@ -1,35 +1,130 @@
-use crate::dijkstra::shortest_path;
+use crate::dijkstra::{
+    astar_weighted_visiting, astar_weighted_with_stats, bidirectional_shortest_path_with_stats,
+    distances_from, distances_from_indexed, shortest_path_with_route, Limits, Outcome, Visitor,
+};
+use crate::rng::Rng;
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
 
 pub fn run(input: &str) -> String {
     let (heightmap, start, end) = parse_input(input);
 
-    let heightmap_height = heightmap.len();
-    let heightmap_width = heightmap[0].len();
     // Take a reference so that closure doesn't take ownership of the Vec.
     let heightmap = &heightmap;
-    // Note: this function returns the points from we could have come from,
-    // because we're calculating distances starting from the *end* point.
-    let neighbors = |&(x, y): &Point| {
-        let curr_height: u8 = heightmap[y][x];
-        [(-1, 0), (1, 0), (0, -1), (0, 1)]
-            .into_iter()
-            .map(move |(dx, dy)| (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy)))
-            .filter(|(x, y)| *y < heightmap_height && *x < heightmap_width)
-            .filter(move |&(nx, ny)| {
-                let neighbor_height = heightmap[ny][nx];
-                curr_height <= neighbor_height + 1
-            })
-    };
+    let neighbors = |&p: &Point| reverse_neighbors(heightmap, p);
 
-    let shortest_dist_from_start = shortest_path(&end, |&p| p == start, neighbors)
-        .expect("there should be a path from start to end");
-    let shortest_dist_from_low_point =
-        shortest_path(&end, |&(x, y)| heightmap[y][x] == 0, neighbors)
-            .expect("there should be a point at height 0 that reaches the end");
+    // Compute all distances to the end point in one go, and use them for both parts.
+    let (point_count, point_index) = point_indexing(heightmap);
+    let distances = distances_from_indexed(&end, neighbors, &point_index, point_count);
+    let shortest_dist_from_start =
+        distances[point_index(&start)].expect("there should be a path from start to end");
+    let shortest_dist_from_low_point = map_points_iter(heightmap[0].len(), heightmap.len())
+        .filter(|&(x, y)| heightmap[y][x] == 0)
+        .filter_map(|point| distances[point_index(&point)])
+        .min()
+        .expect("there should be a point at height 0 that reaches the end");
 
     format!("{shortest_dist_from_start} {shortest_dist_from_low_point}")
 }
 
+// Alternative entry point that draws the shortest route up the hill for part 1.
+pub fn trace(input: &str) -> String {
+    let (heightmap, start, end) = parse_input(input);
+    let heightmap = &heightmap;
+    let neighbors = |&p: &Point| reverse_neighbors(heightmap, p);
+    let (dist, route_to_start) = shortest_path_with_route(&end, |&p| p == start, neighbors)
+        .expect("there should be a path from start to end");
+    format!(
+        "{dist} steps\n{}",
+        draw_route(heightmap, route_to_start.iter().rev())
+    )
+}
+
+// Reports how many points are expanded by A* and bidirectional search compared to plain Dijkstra's
+// for part 1, and how long it takes to compute the distances to all points with a HashMap compared
+// to a dense Vec.
+pub fn search_stats(input: &str) -> String {
+    let (heightmap, start, end) = parse_input(input);
+    let heightmap = &heightmap;
+    let neighbors = |&p: &Point| reverse_neighbors(heightmap, p);
+    let unit_cost_neighbors = |p: &Point| neighbors(p).map(|n| (n, 1));
+    let mut lines = vec![];
+    for (name, use_heuristic) in [("Dijkstra", false), ("A*", true)] {
+        let heuristic = |&p: &Point| {
+            if use_heuristic {
+                manhattan_distance(p, start)
+            } else {
+                0
+            }
+        };
+        let (dist, stats) =
+            astar_weighted_with_stats(&end, |&p| p == start, unit_cost_neighbors, heuristic);
+        let dist = dist.expect("there should be a path from start to end");
+        lines.push(format!("part 1, {name}: {dist} steps; {stats}"));
+    }
+    let (dist, stats) = bidirectional_shortest_path_with_stats(
+        &start,
+        &end,
+        |p: &Point| forward_neighbors(heightmap, *p).map(|n| (n, 1)),
+        unit_cost_neighbors,
+    );
+    let dist = dist.expect("there should be a path from start to end");
+    lines.push(format!("part 1, bidirectional: {dist} steps; {stats}"));
+
+    let instant = Instant::now();
+    let distances = distances_from(&end, neighbors);
+    lines.push(format!(
+        "all distances, HashMap: {} points ({:.1?})",
+        distances.len(),
+        instant.elapsed()
+    ));
+    let instant = Instant::now();
+    let (point_count, point_index) = point_indexing(heightmap);
+    let distances = distances_from_indexed(&end, neighbors, &point_index, point_count);
+    lines.push(format!(
+        "all distances, Vec: {} points ({:.1?})",
+        distances.iter().flatten().count(),
+        instant.elapsed()
+    ));
+    lines.join("\n")
+}
+
+// Note: this function returns the points from we could have come from, because we're calculating
+// distances starting from the *end* point.
+fn reverse_neighbors(heightmap: &[Vec<u8>], (x, y): Point) -> impl Iterator<Item = Point> + '_ {
+    let curr_height = heightmap[y][x];
+    adjacent_points(heightmap, (x, y)).filter(move |&(nx, ny)| curr_height <= heightmap[ny][nx] + 1)
+}
+
+// The points we can climb to from a given point.
+fn forward_neighbors(heightmap: &[Vec<u8>], (x, y): Point) -> impl Iterator<Item = Point> + '_ {
+    let curr_height = heightmap[y][x];
+    adjacent_points(heightmap, (x, y)).filter(move |&(nx, ny)| heightmap[ny][nx] <= curr_height + 1)
+}
+
+fn adjacent_points(heightmap: &[Vec<u8>], (x, y): Point) -> impl Iterator<Item = Point> {
+    let heightmap_height = heightmap.len();
+    let heightmap_width = heightmap[0].len();
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .into_iter()
+        .map(move |(dx, dy)| (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy)))
+        .filter(move |(x, y)| *y < heightmap_height && *x < heightmap_width)
+}
+
+// Maps each point of the heightmap to a contiguous index.
+fn point_indexing(heightmap: &[Vec<u8>]) -> (usize, impl Fn(&Point) -> usize) {
+    let width = heightmap[0].len();
+    (width * heightmap.len(), move |&(x, y): &Point| {
+        y * width + x
+    })
+}
+
+fn manhattan_distance((x1, y1): Point, (x2, y2): Point) -> usize {
+    x1.abs_diff(x2) + y1.abs_diff(y2)
+}
+
 type Point = (usize, usize);
 
 fn parse_input(input: &str) -> (Vec<Vec<u8>>, Point, Point) {
@@ -59,3 +154,180 @@ fn parse_input(input: &str) -> (Vec<Vec<u8>>, Point, Point) {
 fn map_points_iter(width: usize, height: usize) -> impl Iterator<Item = Point> {
     (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
 }
+
+// Draws the route from the start to the end in the same format as the puzzle description.
+fn draw_route<'a>(heightmap: &[Vec<u8>], route: impl Iterator<Item = &'a Point>) -> String {
+    let mut map = vec![vec!['.'; heightmap[0].len()]; heightmap.len()];
+    let route: Vec<_> = route.collect();
+    for (&&(x, y), &&(next_x, next_y)) in route.iter().zip(route.iter().skip(1)) {
+        map[y][x] = match (next_x as isize - x as isize, next_y as isize - y as isize) {
+            (1, 0) => '>',
+            (-1, 0) => '<',
+            (0, 1) => 'v',
+            (0, -1) => '^',
+            _ => unreachable!("route steps should be to adjacent points"),
+        };
+    }
+    if let Some(&&(end_x, end_y)) = route.last() {
+        map[end_y][end_x] = 'E';
+    }
+
+    let rows: Vec<String> = map
+        .into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect();
+    rows.join("\n")
+}
+
+// Alternative entry point that draws the frontier of an A* search from the end to the start
+// expanding over the heightmap, frame by frame, and then the route it found.
+pub fn animate(input: &str) -> String {
+    let (heightmap, start, end) = parse_input(input);
+    let heightmap = &heightmap;
+    let mut animation = FrontierAnimation {
+        tiles: heightmap
+            .iter()
+            .map(|row| row.iter().map(|_| Unexplored).collect())
+            .collect(),
+        predecessors: heightmap
+            .iter()
+            .map(|row| row.iter().map(|_| None).collect())
+            .collect(),
+        expanded_count: 0,
+    };
+    let (outcome, stats) = astar_weighted_visiting(
+        &end,
+        |&p| p == start,
+        |&p: &Point| reverse_neighbors(heightmap, p).map(|n| (n, 1)),
+        |&p: &Point| manhattan_distance(p, start),
+        &Limits::default(),
+        &mut animation,
+    );
+    let Outcome::Found(dist) = outcome else {
+        panic!("there should be a path from start to end")
+    };
+    format!("{dist} steps; {stats}")
+}
+
+struct FrontierAnimation {
+    tiles: Vec<Vec<SearchTile>>,
+    // Where each point was last reached from, to draw the route once the goal is found.
+    predecessors: Vec<Vec<Option<Point>>>,
+    expanded_count: usize,
+}
+
+#[derive(Clone, Copy)]
+enum SearchTile {
+    Unexplored,
+    Frontier,
+    Expanded,
+    Route,
+}
+use SearchTile::*;
+
+impl Visitor<Point, usize> for FrontierAnimation {
+    fn on_push(&mut self, &(x, y): &Point, _distance: usize) {
+        self.tiles[y][x] = Frontier;
+    }
+
+    fn on_pop(&mut self, &(x, y): &Point, _distance: usize) {
+        self.tiles[y][x] = Expanded;
+        self.expanded_count += 1;
+        if self.expanded_count.is_multiple_of(10) {
+            self.print_frame();
+        }
+    }
+
+    fn on_relax(&mut self, &from: &Point, &(x, y): &Point, _distance: usize) {
+        self.predecessors[y][x] = Some(from);
+    }
+
+    fn on_goal(&mut self, &goal: &Point, _distance: usize) {
+        let mut point = Some(goal);
+        while let Some((x, y)) = point {
+            self.tiles[y][x] = Route;
+            point = self.predecessors[y][x];
+        }
+        self.print_frame();
+    }
+}
+
+impl FrontierAnimation {
+    fn print_frame(&self) {
+        let use_color = env::var("NO_COLOR").is_err();
+        let [unexplored_str, frontier_str, expanded_str, route_str] = if use_color {
+            [
+                "\x1B[2m.\x1B[0m",
+                "\x1B[1;33m+\x1B[0m",
+                "\x1B[34m#\x1B[0m",
+                "\x1B[1;32m@\x1B[0m",
+            ]
+        } else {
+            [".", "+", "#", "@"]
+        };
+        let mut output = String::new();
+        for row in self.tiles.iter() {
+            output.extend(row.iter().map(|tile| match tile {
+                Unexplored => unexplored_str,
+                Frontier => frontier_str,
+                Expanded => expanded_str,
+                Route => route_str,
+            }));
+            output.push('\n');
+        }
+        print!("\x1B[2J{output}");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// Generates a heightmap of `size` columns with a few hills, the highest of which is the end point.
+// Heights change by at most 1 between neighbors, so every point can reach every other point.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    // The map needs to be big enough to go down from height 25 at the end to 0 at the start.
+    let width = size.max(40);
+    let height = (width / 3).max(14);
+    let end = (rng.below(width), rng.below(height));
+    let far_points: Vec<_> = map_points_iter(width, height)
+        .filter(|&p| manhattan_distance(p, end) >= 25)
+        .collect();
+    let start = *rng.choose(&far_points);
+    let mut peaks = vec![(end, 25)];
+    for _ in 0..rng.range(0..=5) {
+        let peak = (rng.below(width), rng.below(height));
+        peaks.push((peak, rng.range(5..=20) as usize));
+    }
+
+    // Both the hills and the distance to the start point change by 1 at most between neighbors,
+    // so the max and min of them do too.
+    let heightmap: Vec<Vec<usize>> = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let hills_height = peaks
+                        .iter()
+                        .map(|&(peak, peak_height)| {
+                            peak_height.saturating_sub(manhattan_distance((x, y), peak))
+                        })
+                        .max()
+                        .unwrap();
+                    hills_height.min(manhattan_distance((x, y), start))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut output = String::new();
+    for (y, row) in heightmap.iter().enumerate() {
+        for (x, &point_height) in row.iter().enumerate() {
+            output.push(if (x, y) == start {
+                'S'
+            } else if (x, y) == end {
+                'E'
+            } else {
+                (b'a' + point_height as u8) as char
+            });
+        }
+        output.push('\n');
+    }
+    output
+}
@@ -1,11 +1,18 @@
+use crate::rng::Rng;
 use std::cmp::Ordering;
 
 pub fn run(input: &str) -> String {
+    let parse = |packet| {
+        parse_packet(packet).unwrap_or_else(|err| panic!("invalid packet '{packet}': {err}"))
+    };
     let pairs: Vec<(Value, Value)> = input
         .split("\n\n")
         .map(|s| {
-            let (left, right) = s.split_once('\n').expect("expected a pair of values");
-            (parse_packet(left), parse_packet(right))
+            let (left, right) = s
+                .trim_end()
+                .split_once('\n')
+                .expect("expected a pair of values");
+            (parse(left), parse(right))
         })
         .collect();
 
@@ -16,7 +23,7 @@ pub fn run(input: &str) -> String {
         .map(|(index, _pair)| index + 1)
         .sum();
 
-    let divider_packets = [parse_packet("[[2]]"), parse_packet("[[6]]")];
+    let divider_packets = [parse("[[2]]"), parse("[[6]]")];
 
     let mut all_packets: Vec<_> = pairs
         .iter()
@@ -28,21 +35,33 @@ pub fn run(input: &str) -> String {
     let decoder_key: usize = all_packets
         .iter()
         .enumerate()
-        .filter(|(_index, value)| divider_packets.contains(value))
+        // Look for the divider packets themselves, as other packets can be equal to them, like [2].
+        .filter(|(_index, value)| {
+            divider_packets
+                .iter()
+                .any(|divider| std::ptr::eq(**value, divider))
+        })
         .map(|(index, _value)| index + 1)
         .product();
 
     format!("{ordered_pairs_indices_sum} {decoder_key}")
 }
 
-#[derive(Eq, PartialEq)]
+// Entry point for fuzzing the packets parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    for line in input.lines() {
+        let _ = parse_packet(line);
+    }
+}
+
+#[derive(Clone, Debug, Eq)]
 enum Value {
     Num(u32),
     List(Vec<Value>),
 }
 use Value::*;
 
-fn parse_packet(s: &str) -> Value {
+fn parse_packet(s: &str) -> Result<Value, String> {
     let mut bytes = s.bytes().peekable();
     let mut list_stack: Vec<Vec<Value>> = Vec::new();
     while let Some(ch) = bytes.next() {
@@ -51,32 +70,35 @@ fn parse_packet(s: &str) -> Value {
                 list_stack.push(Vec::new());
             }
             b']' => {
-                let Some(last_list) = list_stack.pop() else {
-                    panic!("unmatched ]")
-                };
+                let last_list = list_stack.pop().ok_or("unmatched ]")?;
                 let value = Value::List(last_list);
                 if let Some(parent_list) = list_stack.last_mut() {
                     parent_list.push(value);
-                } else {
+                } else if bytes.peek().is_none() {
                     // List is topmost list.
-                    return value;
+                    return Ok(value);
+                } else {
+                    return Err("unexpected characters after the packet".to_string());
                 }
             }
             b',' => {}
             d if d.is_ascii_digit() => {
                 let mut n = (d - b'0') as u32;
                 while let Some(next_d) = bytes.next_if(|ch| ch.is_ascii_digit()) {
-                    n = n * 10 + (next_d - b'0') as u32;
+                    n = n
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add((next_d - b'0') as u32))
+                        .ok_or("number too big")?;
                 }
-                let Some(list) = list_stack.last_mut() else {
-                    panic!("number must appear inside a list")
-                };
+                let list = list_stack
+                    .last_mut()
+                    .ok_or("number must appear inside a list")?;
                 list.push(Value::Num(n))
             }
-            _ => panic!("unexpected character '{}'", ch as char),
+            _ => return Err(format!("unexpected character '{}'", ch.escape_ascii())),
         }
     }
-    panic!("unclosed list")
+    Err("unclosed list".to_string())
 }
 
 impl Ord for Value {
@@ -90,6 +112,13 @@ impl Ord for Value {
     }
 }
 
+// Equality has to agree with the order, where e.g. 1 and [1] are equal, so it can't be derived.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -106,3 +135,117 @@ fn cmp_lists(l_values: &[Value], r_values: &[Value]) -> Ordering {
         }
     }
 }
+
+// Generates `size` pairs of packets.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let pairs: Vec<_> = (0..size.max(1))
+        .map(|_| {
+            let mut left = String::new();
+            let mut right = String::new();
+            write_random_list(rng, 0, &mut left);
+            // Make the packets in a pair similar sometimes, so that comparing them needs to go
+            // deeper than the first element.
+            if rng.chance(0.3) {
+                right = left.clone();
+                let last_num_index = right.rfind(|ch: char| ch.is_ascii_digit());
+                if let Some(i) = last_num_index {
+                    right.replace_range(i..=i, &rng.below(10).to_string());
+                }
+            } else {
+                write_random_list(rng, 0, &mut right);
+            }
+            format!("{left}\n{right}\n")
+        })
+        .collect();
+    pairs.join("\n")
+}
+
+fn write_random_list(rng: &mut Rng, depth: usize, output: &mut String) {
+    output.push('[');
+    for i in 0..rng.below(5) {
+        if i > 0 {
+            output.push(',');
+        }
+        if depth < 4 && rng.chance(0.3) {
+            write_random_list(rng, depth + 1, output);
+        } else {
+            *output += &rng.below(11).to_string();
+        }
+    }
+    output.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, Strategy};
+
+    // Random packets like the ones of the generated inputs, with small numbers so that there are
+    // plenty of ties.
+    struct Packets;
+
+    impl Strategy for Packets {
+        type Value = Value;
+
+        fn generate(&self, rng: &mut Rng) -> Value {
+            let mut packet = String::new();
+            write_random_list(rng, 0, &mut packet);
+            parse_packet(&packet).unwrap()
+        }
+
+        // Lists shrink into their elements, into themselves without one of their elements, or with
+        // one of them shrunk. Numbers shrink towards 0.
+        fn shrink(&self, value: &Value) -> Vec<Value> {
+            match value {
+                Num(0) => vec![],
+                Num(n) => vec![Num(0), Num(n / 2)],
+                List(values) => {
+                    let mut candidates = values.clone();
+                    for i in 0..values.len() {
+                        let mut removed = values.clone();
+                        removed.remove(i);
+                        candidates.push(List(removed));
+                    }
+                    for (i, v) in values.iter().enumerate() {
+                        for simpler in self.shrink(v) {
+                            let mut shrunk = values.clone();
+                            shrunk[i] = simpler;
+                            candidates.push(List(shrunk));
+                        }
+                    }
+                    candidates
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packet_order_is_total() {
+        check(&(Packets, Packets, Packets), |(a, b, c)| {
+            let reflexive = a.cmp(a) == Ordering::Equal;
+            let antisymmetric = a.cmp(b) == b.cmp(a).reverse();
+            let transitive = !(a <= b && b <= c) || a <= c;
+            let consistent_eq = (a == b) == (a.cmp(b) == Ordering::Equal);
+            reflexive && antisymmetric && transitive && consistent_eq
+        });
+    }
+
+    #[test]
+    fn equality_follows_the_order() {
+        let parse = |s| parse_packet(s).unwrap();
+        assert_eq!(parse("[1]"), parse("[[1]]"));
+        assert_eq!(parse("[[2]]"), parse("[2]"));
+        assert_ne!(parse("[1]"), parse("[1,1]"));
+    }
+
+    #[test]
+    fn packets_must_be_a_single_list() {
+        assert_eq!(
+            parse_packet("[1,[2]]"),
+            Ok(List(vec![Num(1), List(vec![Num(2)])]))
+        );
+        for packet in ["[1]]]x", "[1][2]", "[1] ", "[1", "1", "]"] {
+            assert!(parse_packet(packet).is_err(), "{packet} should be rejected");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::rng::Rng;
 use std::{env, thread, time};
 
 pub fn run(input: &str) -> String {
@@ -134,3 +135,34 @@ fn print_map_frame(map: &Map) {
     print!("\x1B[2J{output}");
     thread::sleep(time::Duration::from_millis(20));
 }
+
+// Generates `size` rock paths below the sand source.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let max_y = rng.range(10..=170) as usize;
+    (0..size.max(1))
+        .map(|_| {
+            // Keep the rocks within the triangle the sand can reach.
+            let mut y = rng.range(2..=max_y as i64) as usize;
+            let mut x = SAND_POUR_X - y + rng.below(2 * y + 1);
+            let mut points = vec![format!("{x},{y}")];
+            for i in 0..rng.range(1..=5) {
+                let len = rng.range(1..=8) as usize;
+                if i % 2 == 0 {
+                    x = if rng.chance(0.5) {
+                        (x + len).min(SAND_POUR_X + y)
+                    } else {
+                        x.saturating_sub(len).max(SAND_POUR_X - y)
+                    };
+                } else {
+                    y = if rng.chance(0.5) {
+                        (y + len).min(max_y)
+                    } else {
+                        y.saturating_sub(len).max(x.abs_diff(SAND_POUR_X)).max(2)
+                    };
+                }
+                points.push(format!("{x},{y}"));
+            }
+            points.join(" -> ") + "\n"
+        })
+        .collect()
+}
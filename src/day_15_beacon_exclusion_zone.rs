@@ -1,3 +1,4 @@
+use crate::rng::Rng;
 use std::collections::HashSet;
 
 use rayon::prelude::*;
@@ -99,3 +100,87 @@ fn get_contiguous_exclusion_row_segments(sensors: &[Sensor], y: i64) -> Vec<(i64
     }
     segments
 }
+
+// Generates a sensor report where every position of the search area is covered by some sensor,
+// except for the distress beacon, and the beacon reported by each sensor is the only closest one.
+// With a `size` under 20 the input is of the sample's scale, otherwise it is of the real input's
+// scale. There are `size` sensors, and at least 4.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let is_sample = size < 20;
+    let (area_size, row) = if is_sample {
+        (20, 10)
+    } else {
+        (4_000_000, 2_000_000)
+    };
+    // The row that is counted must be a single segment, so the distress beacon can't be on it.
+    let (px, py) = loop {
+        let position = (rng.range(0..=area_size), rng.range(0..=area_size));
+        if position.1 != row {
+            break position;
+        }
+    };
+
+    // Four big sensors, one on each diagonal direction from the distress beacon, whose ranges just
+    // miss it. Being at least `area_size` away on both axes, between them they cover the rest of
+    // the search area. Each one's beacon is on the tip of its range that is furthest from the
+    // other ranges, so it's outside them.
+    let mut sensors = vec![];
+    for (dir_x, dir_y) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+        let dx = rng.range(area_size..=2 * area_size - 1);
+        let dy = rng.range(area_size..=2 * area_size - 1);
+        let position = (px + dir_x * dx, py + dir_y * dy);
+        let distance = dx + dy - 1;
+        sensors.push(Sensor {
+            position,
+            beacon_position: (position.0, position.1 + dir_y * distance),
+            beacon_distance: distance,
+        });
+    }
+    let outer_beacons: Vec<_> = sensors.iter().map(|s| s.beacon_position).collect();
+    while sensors.len() < size {
+        add_sensor_near_outer_beacon(rng, &mut sensors, &outer_beacons, area_size);
+    }
+
+    rng.shuffle(&mut sensors);
+    sensors
+        .iter()
+        .map(|s| {
+            let ((sx, sy), (bx, by)) = (s.position, s.beacon_position);
+            format!("Sensor at x={sx}, y={sy}: closest beacon is at x={bx}, y={by}\n")
+        })
+        .collect()
+}
+
+// Adds a small sensor past one of the beacons of the big sensors, so its range stays away from the
+// search area and doesn't change the exclusion segments of any of its rows.
+fn add_sensor_near_outer_beacon(
+    rng: &mut Rng,
+    sensors: &mut Vec<Sensor>,
+    outer_beacons: &[Point],
+    area_size: i64,
+) {
+    loop {
+        let beacon = *rng.choose(outer_beacons);
+        let dir_y = if beacon.1 > area_size { 1 } else { -1 };
+        let dy = rng.range(1..=area_size / 2);
+        let dx = rng.range(-dy..=dy);
+        let position = (beacon.0 + dx, beacon.1 + dir_y * dy);
+        let distance = manhattan_distance(position, beacon);
+        let is_only_closest = outer_beacons
+            .iter()
+            .all(|&b| b == beacon || manhattan_distance(position, b) > distance);
+        let is_new_position = sensors.iter().all(|s| s.position != position);
+        if is_only_closest && is_new_position {
+            sensors.push(Sensor {
+                position,
+                beacon_position: beacon,
+                beacon_distance: distance,
+            });
+            return;
+        }
+    }
+}
+
+fn manhattan_distance((x1, y1): Point, (x2, y2): Point) -> i64 {
+    (x1 - x2).abs() + (y1 - y2).abs()
+}
@@ -1,106 +1,303 @@
-use crate::dijkstra::shortest_path;
+use crate::bitset::{Bitset, DynBitset};
+use crate::branch_and_bound::{self, Config, Order, Problem};
+use crate::graph::{self, CompressedGraph};
+use crate::rng::Rng;
 use rayon::prelude::*;
-use std::{collections::HashMap, mem};
+use rustc_hash::FxHashMap;
+use std::collections::HashMap;
 
 // Note: this solution was ~stolen from~ heavily inspired by
 // https://old.reddit.com/r/adventofcode/comments/zn6k1l/2022_day_16_solutions/j2xhog7/
 pub fn run(input: &str) -> String {
+    let cave = parse_cave(input);
+    let (part_1_ans, part_2_ans) = match cave.working_valves.len() {
+        0..=32 => max_pressures::<u32>(&cave),
+        33..=64 => max_pressures::<u64>(&cave),
+        65..=128 => max_pressures::<u128>(&cave),
+        _ => max_pressures::<DynBitset>(&cave),
+    };
+
+    format!("{part_1_ans} {part_2_ans}")
+}
+
+// Reports how many paths each pruning strategy explores.
+pub fn search_stats(input: &str) -> String {
+    let cave = parse_cave(input);
+    match cave.working_valves.len() {
+        0..=32 => search_stats_with::<u32>(&cave),
+        33..=64 => search_stats_with::<u64>(&cave),
+        65..=128 => search_stats_with::<u128>(&cave),
+        _ => search_stats_with::<DynBitset>(&cave),
+    }
+}
+
+type ValveId = usize;
+
+// Up to this many working valves, the max pressure for each set of open valves is stored in a dense
+// table of 2^N entries. Above that, only the reached sets are stored in a map.
+const DENSE_MEMO_MAX_VALVES: usize = 16;
+
+struct Valve {
+    id: ValveId,
+    flow_rate: u64,
+    connected_valves: Vec<ValveId>,
+}
+
+// The valve graph compressed down to the start valve and the working valves, which are the only
+// ones we care about.
+struct Cave {
+    flow_rates: Vec<u64>,
+    start_valve_id: ValveId,
+    working_valves: Vec<ValveId>,
+    // Distances from each valve to each reachable working valve.
+    distances: Vec<Vec<(ValveId, usize)>>,
+}
+
+fn parse_cave(input: &str) -> Cave {
     let (valves, start_valve_id) = parse_valves(input);
 
-    let working_valves: Vec<_> = valves
-        .iter()
-        .filter(|valve| valve.flow_rate > 0)
-        .map(|valve| valve.id)
-        .collect();
+    let mut interesting_valves = vec![start_valve_id];
+    interesting_valves.extend(
+        valves
+            .iter()
+            .filter(|valve| valve.flow_rate > 0 && valve.id != start_valve_id)
+            .map(|valve| valve.id),
+    );
+    let adjacency: Vec<_> = valves.iter().map(|v| v.connected_valves.clone()).collect();
+    let graph = CompressedGraph::new(&graph::all_pairs_bfs(&adjacency), &interesting_valves);
 
-    let distances: Vec<Vec<_>> = valves
+    let flow_rates: Vec<_> = graph.nodes.iter().map(|&id| valves[id].flow_rate).collect();
+    let working_valves: Vec<_> = (0..graph.nodes.len())
+        .filter(|&id| flow_rates[id] > 0)
+        .collect();
+    let distances = graph
+        .distances
         .iter()
-        .map(|valve| {
+        .map(|distances_from_valve| {
             working_valves
                 .iter()
-                .map(|&other_id| {
-                    let successors = |&id: &ValveId| valves[id].connected_valves.iter().cloned();
-                    let d = shortest_path(&valve.id, |&id| id == other_id, successors).unwrap();
-                    (other_id, d)
-                })
+                .filter_map(|&other_id| Some((other_id, distances_from_valve[other_id]?)))
                 .collect()
         })
         .collect();
 
-    let mut valve_bitmasks = vec![0; valves.len()];
-    for (bit_index, id) in working_valves.iter().enumerate() {
-        assert!(bit_index < BITMASK_BITS, "too many valves for bitmask size");
-        valve_bitmasks[*id] = 1 << bit_index;
+    Cave {
+        flow_rates,
+        start_valve_id: graph.index_of(start_valve_id).unwrap(),
+        working_valves,
+        distances,
     }
+}
+
+fn max_pressures<B: Bitset + Send + Sync>(cave: &Cave) -> (u64, u64) {
+    let valve_bitmasks = valve_bitmasks::<B>(cave);
+    let search = PathSearch::new(cave, &valve_bitmasks);
 
-    let part_1_max_pressures =
-        visit_all_paths(start_valve_id, 30, &valves, &distances, &valve_bitmasks);
-    let part_1_ans = part_1_max_pressures.iter().map(|(_b, p)| p).max().unwrap();
+    let part_1_ans =
+        branch_and_bound::maximize(&search, search.initial_state(30), Config::default()).value;
 
-    let part_2_max_pressures =
-        visit_all_paths(start_valve_id, 26, &valves, &distances, &valve_bitmasks);
+    // For part 2 we need the max pressure for every set of open valves, so all paths need to be
+    // visited, not only the promising ones.
+    let mut memo = if cave.working_valves.len() <= DENSE_MEMO_MAX_VALVES {
+        PressureMemo::Dense(vec![0; 1 << cave.working_valves.len()])
+    } else {
+        PressureMemo::Sparse(FxHashMap::default())
+    };
+    let initial_state = search.initial_state(26);
+    branch_and_bound::maximize_visiting(&search, initial_state, EXHAUSTIVE, |state| {
+        let (_valve, _remaining_minutes, open_valves_bitmask, released_pressure) = state;
+        memo.update(open_valves_bitmask, *released_pressure)
+    });
+    let part_2_max_pressures = memo.into_max_pressures();
     let part_2_ans = part_2_max_pressures
         .par_iter()
         .flat_map(|(bitmask_1, pressure_1)| {
             part_2_max_pressures
                 .par_iter()
-                .filter(move |&(bitmask_2, _pressure_2)| (bitmask_1 & bitmask_2) == 0)
+                .filter(move |&(bitmask_2, _pressure_2)| bitmask_1.is_disjoint(bitmask_2))
                 .map(move |(_bitmask_2, pressure_2)| pressure_1 + pressure_2)
         })
         .max()
         .unwrap_or(0);
 
-    format!("{part_1_ans} {part_2_ans}")
+    (part_1_ans, part_2_ans)
 }
 
-type ValveId = usize;
-type Bitmask = u16;
-const BITMASK_BITS: usize = mem::size_of::<Bitmask>() * 8;
+const EXHAUSTIVE: Config = Config {
+    order: Order::DepthFirst,
+    bound_pruning: false,
+    dominance_pruning: true,
+    parallel: false,
+};
 
-struct Valve {
-    id: ValveId,
-    flow_rate: u64,
-    connected_valves: Vec<ValveId>,
+fn search_stats_with<B: Bitset + Send + Sync>(cave: &Cave) -> String {
+    let valve_bitmasks = valve_bitmasks::<B>(cave);
+    let search = PathSearch::new(cave, &valve_bitmasks);
+    let no_dominance = Config {
+        dominance_pruning: false,
+        ..Config::default()
+    };
+    let configs = [
+        ("depth-first", Config::default()),
+        (
+            "best-first",
+            Config {
+                order: Order::BestFirst,
+                ..Config::default()
+            },
+        ),
+        ("depth-first, no dominance", no_dominance),
+        ("depth-first, no bound", EXHAUSTIVE),
+        (
+            "depth-first, no pruning",
+            Config {
+                dominance_pruning: false,
+                ..EXHAUSTIVE
+            },
+        ),
+    ];
+    configs
+        .iter()
+        .map(|&(config_name, config)| {
+            let solution = branch_and_bound::maximize(&search, search.initial_state(30), config);
+            format!(
+                "part 1, {config_name}: {} pressure; {}",
+                solution.value, solution.stats
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn visit_all_paths(
-    initial_valve: ValveId,
-    total_minutes: u64,
-    valves: &[Valve],
-    distances: &[Vec<(ValveId, usize)>],
-    valve_bitmasks: &[Bitmask],
-) -> Vec<(Bitmask, u64)> {
-    let mut max_pressures = vec![0; 1 << BITMASK_BITS];
-    let mut stack = vec![(initial_valve, total_minutes, 0, 0)];
+fn valve_bitmasks<B: Bitset>(cave: &Cave) -> Vec<B> {
+    let mut valve_bitmasks = vec![B::default(); cave.flow_rates.len()];
+    for (bit_index, &id) in cave.working_valves.iter().enumerate() {
+        valve_bitmasks[id] = B::singleton(bit_index);
+    }
+    valve_bitmasks
+}
 
-    while let Some(state) = stack.pop() {
-        let (current_valve, remaining_minutes, open_valves_bitmask, released_pressure) = state;
+// Max released pressure for each set of open valves.
+enum PressureMemo<B> {
+    Dense(Vec<u64>),
+    Sparse(FxHashMap<B, u64>),
+}
 
-        let max_val = &mut max_pressures[open_valves_bitmask as usize];
+impl<B: Bitset> PressureMemo<B> {
+    fn update(&mut self, open_valves: &B, released_pressure: u64) {
+        let max_val = match self {
+            PressureMemo::Dense(max_pressures) => &mut max_pressures[open_valves.to_dense_index()],
+            PressureMemo::Sparse(max_pressures) => {
+                max_pressures.entry(open_valves.clone()).or_default()
+            }
+        };
         *max_val = (*max_val).max(released_pressure);
+    }
 
-        for &(other_valve, dist) in distances[current_valve].iter() {
-            let not_enough_time = dist as u64 + 1 > remaining_minutes;
-            let already_open = valve_bitmasks[other_valve] & open_valves_bitmask != 0;
-            if not_enough_time || already_open {
-                continue;
-            }
-            let new_remaining_minutes = remaining_minutes - dist as u64 - 1;
-            stack.push((
+    fn into_max_pressures(self) -> Vec<(B, u64)> {
+        match self {
+            PressureMemo::Dense(max_pressures) => max_pressures
+                .iter()
+                .enumerate()
+                .filter(|&(_i, pressure)| *pressure > 0)
+                .map(|(i, pressure)| (B::from_dense_index(i), *pressure))
+                .collect(),
+            PressureMemo::Sparse(max_pressures) => max_pressures
+                .into_iter()
+                .filter(|&(_, pressure)| pressure > 0)
+                .collect(),
+        }
+    }
+}
+
+struct PathSearch<'a, B> {
+    start_valve_id: ValveId,
+    flow_rates: &'a [u64],
+    distances: &'a [Vec<(ValveId, usize)>],
+    valve_bitmasks: &'a [B],
+}
+
+// (current valve, remaining minutes, open valves, released pressure)
+type PathState<B> = (ValveId, u64, B, u64);
+
+impl<B: Bitset> Problem for PathSearch<'_, B> {
+    type State = PathState<B>;
+    // Reaching the same valve at the same time with the same open valves leads to the same paths,
+    // so only the one with the most released pressure is worth exploring.
+    type Key = (ValveId, u64, B);
+
+    fn value(&self, state: &PathState<B>) -> u64 {
+        state.3
+    }
+
+    // Optimistically assume every closed valve could be opened by going straight to it.
+    fn upper_bound(&self, state: &PathState<B>) -> u64 {
+        let (current_valve, remaining_minutes, ref open_valves_bitmask, released_pressure) = *state;
+        let reachable_pressure: u64 = self
+            .openable_valves(current_valve, remaining_minutes, open_valves_bitmask)
+            .map(|(other_valve, new_remaining_minutes)| {
+                new_remaining_minutes * self.flow_rates[other_valve]
+            })
+            .sum();
+        released_pressure + reachable_pressure
+    }
+
+    fn successors(&self, state: &PathState<B>, push: &mut impl FnMut(PathState<B>)) {
+        let (current_valve, remaining_minutes, ref open_valves_bitmask, released_pressure) = *state;
+        for (other_valve, new_remaining_minutes) in
+            self.openable_valves(current_valve, remaining_minutes, open_valves_bitmask)
+        {
+            push((
                 other_valve,
                 new_remaining_minutes,
-                open_valves_bitmask | valve_bitmasks[other_valve],
-                released_pressure + new_remaining_minutes * valves[other_valve].flow_rate,
+                open_valves_bitmask.union(&self.valve_bitmasks[other_valve]),
+                released_pressure + new_remaining_minutes * self.flow_rates[other_valve],
             ));
         }
     }
 
-    max_pressures
-        .iter()
-        .enumerate()
-        .filter(|&(_i, pressure)| *pressure > 0)
-        .map(|(i, pressure)| (i as Bitmask, *pressure))
-        .collect()
+    fn dominance_key(&self, state: &PathState<B>) -> Option<Self::Key> {
+        let (current_valve, remaining_minutes, ref open_valves_bitmask, _) = *state;
+        Some((
+            current_valve,
+            remaining_minutes,
+            open_valves_bitmask.clone(),
+        ))
+    }
+}
+
+impl<'a, B: Bitset> PathSearch<'a, B> {
+    fn new(cave: &'a Cave, valve_bitmasks: &'a [B]) -> Self {
+        PathSearch {
+            start_valve_id: cave.start_valve_id,
+            flow_rates: &cave.flow_rates,
+            distances: &cave.distances,
+            valve_bitmasks,
+        }
+    }
+
+    fn initial_state(&self, total_minutes: u64) -> PathState<B> {
+        (self.start_valve_id, total_minutes, B::default(), 0)
+    }
+
+    // Returns the closed valves that can be reached and opened in time, with the remaining minutes
+    // after opening them.
+    fn openable_valves<'s>(
+        &'s self,
+        current_valve: ValveId,
+        remaining_minutes: u64,
+        open_valves_bitmask: &'s B,
+    ) -> impl Iterator<Item = (ValveId, u64)> + 's {
+        self.distances[current_valve]
+            .iter()
+            .filter(move |&&(other_valve, dist)| {
+                let not_enough_time = dist as u64 + 1 > remaining_minutes;
+                let already_open =
+                    !self.valve_bitmasks[other_valve].is_disjoint(open_valves_bitmask);
+                !not_enough_time && !already_open
+            })
+            .map(move |&(other_valve, dist)| (other_valve, remaining_minutes - dist as u64 - 1))
+    }
 }
 
 fn parse_valves(input: &str) -> (Vec<Valve>, ValveId) {
@@ -128,3 +325,56 @@ fn parse_valves(input: &str) -> (Vec<Valve>, ValveId) {
         .collect();
     (valves, valve_ids["AA"])
 }
+
+// Generates a connected cave of `size` valves, of which up to 15 have a working flow rate. The
+// start valve, AA, is always stuck.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let valve_count = size.clamp(2, 26 * 26);
+    let mut names = vec!["AA".to_string()];
+    while names.len() < valve_count {
+        let name: String = (0..2)
+            .map(|_| (b'A' + rng.below(26) as u8) as char)
+            .collect();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let mut flow_rates = vec![0; valve_count];
+    let working_valve_count = rng.range(1..=(valve_count as i64 - 1).min(15)) as usize;
+    for flow_rate in flow_rates[1..=working_valve_count].iter_mut() {
+        *flow_rate = rng.range(1..=25);
+    }
+    rng.shuffle(&mut flow_rates[1..]);
+
+    // A random tree keeps all valves connected, and a few more tunnels add some loops.
+    let mut tunnels = vec![vec![]; valve_count];
+    let mut connect = |a: usize, b: usize| {
+        if a != b && !tunnels[a].contains(&b) {
+            tunnels[a].push(b);
+            tunnels[b].push(a);
+        }
+    };
+    for valve in 1..valve_count {
+        connect(valve, rng.below(valve));
+    }
+    for _ in 0..valve_count / 4 {
+        connect(rng.below(valve_count), rng.below(valve_count));
+    }
+
+    let mut lines: Vec<_> = (0..valve_count)
+        .map(|valve| {
+            let connected_names: Vec<_> = tunnels[valve].iter().map(|&v| &names[v][..]).collect();
+            let tunnels_description = match connected_names[..] {
+                [name] => format!("tunnel leads to valve {name}"),
+                _ => format!("tunnels lead to valves {}", connected_names.join(", ")),
+            };
+            format!(
+                "Valve {} has flow rate={}; {tunnels_description}\n",
+                names[valve], flow_rates[valve]
+            )
+        })
+        .collect();
+    rng.shuffle(&mut lines);
+    lines.concat()
+}
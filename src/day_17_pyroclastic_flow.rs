@@ -1,3 +1,4 @@
+use crate::rng::Rng;
 use std::collections::HashMap;
 
 pub fn run(input: &str) -> String {
@@ -9,6 +10,16 @@ pub fn run(input: &str) -> String {
 }
 
 fn run_rock_simulation(input: &str, total_rock_falls: u64) -> u64 {
+    try_run_rock_simulation(input, total_rock_falls, u64::MAX)
+        .expect("should find a cycle before running out of rocks")
+}
+
+// Gives up and returns None if no cycle is detected in the first `max_simulated_rocks` rock falls.
+fn try_run_rock_simulation(
+    input: &str,
+    total_rock_falls: u64,
+    max_simulated_rocks: u64,
+) -> Option<u64> {
     let mut rock_iter = ROCKS.iter().copied().enumerate().cycle();
     let mut jet_iter = input.chars().enumerate().cycle();
     let mut chamber: Vec<Row> = vec![];
@@ -22,6 +33,9 @@ fn run_rock_simulation(input: &str, total_rock_falls: u64) -> u64 {
         if cyclic_height.is_some() {
             continue;
         }
+        if total_rock_falls - remaining_rock_falls >= max_simulated_rocks {
+            return None;
+        }
         if chamber.len() < CHAMBER_TOP_HEIGHT {
             continue;
         }
@@ -57,7 +71,7 @@ fn run_rock_simulation(input: &str, total_rock_falls: u64) -> u64 {
         );
     }
 
-    chamber.len() as u64 + cyclic_height.unwrap_or(0)
+    Some(chamber.len() as u64 + cyclic_height.unwrap_or(0))
 }
 
 type Row = u8;
@@ -163,3 +177,20 @@ fn rock_collides(rock: Rock, rock_height: usize, chamber: &[Row]) -> bool {
         rock_row & chamber_row != 0
     })
 }
+
+// Generates a jet pattern of at least 20 pushes. Note that, like the puzzle input, it doesn't end
+// with a newline. Some patterns never let the chamber's top get sealed, for example if the rocks
+// never get pushed to one of the sides, so the simulation wouldn't find a cycle. Those are
+// discarded.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let len = size.max(20);
+    loop {
+        let jets: String = (0..len)
+            .map(|_| if rng.chance(0.5) { '<' } else { '>' })
+            .collect();
+        let max_simulated_rocks = (len * ROCKS.len() * 10 + 10_000) as u64;
+        if try_run_rock_simulation(&jets, 1_000_000_000_000, max_simulated_rocks).is_some() {
+            return jets;
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use crate::dijkstra::distances_from;
+use crate::rng::Rng;
+
 pub fn run(input: &str) -> String {
     let droplet_points: Vec<_> = input.lines().map(parse_point).collect();
 
@@ -53,16 +56,14 @@ fn count_exposed_faces(droplet_points: &[Point], grid: &Grid) -> usize {
 
 fn fill_air_pockets(grid: &Grid) -> Grid {
     let grid_size = grid.len();
+    // Flood-fill the air that surrounds the droplet from a grid corner. Everything else is either
+    // lava or an air pocket.
+    let outside_air = distances_from(&(0, 0, 0), |&point| {
+        neighbors(point, grid_size).filter(|&(x, y, z)| !grid[x][y][z])
+    });
     let mut filled_grid = vec![vec![vec![true; grid_size]; grid_size]; grid_size];
-    let mut unvisited = vec![(0, 0, 0)];
-    while let Some((x, y, z)) = unvisited.pop() {
-        if grid[x][y][z] || !filled_grid[x][y][z] {
-            continue;
-        }
+    for &(x, y, z) in outside_air.keys() {
         filled_grid[x][y][z] = false;
-        for (nx, ny, nz) in neighbors((x, y, z), grid_size) {
-            unvisited.push((nx, ny, nz))
-        }
     }
     filled_grid
 }
@@ -79,3 +80,34 @@ fn neighbors((x, y, z): Point, grid_size: usize) -> impl Iterator<Item = Point>
     .into_iter()
     .filter(move |&(nx, ny, nz)| nx < grid_size && ny < grid_size && nz < grid_size)
 }
+
+// Generates a lumpy droplet that fits in a `size` x `size` x `size` cube, made of a few
+// overlapping balls, which tends to leave some air pockets between them.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let size = size.max(2) as i64;
+    let balls: Vec<_> = (0..rng.range(1..=8))
+        .map(|_| {
+            let center = [(); 3].map(|_| rng.range(0..=size - 1));
+            let radius = rng.range(1..=(size / 3).max(1));
+            (center, radius)
+        })
+        .collect();
+    let mut output = String::new();
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let in_some_ball = balls.iter().any(|&([cx, cy, cz], radius)| {
+                    (x - cx).pow(2) + (y - cy).pow(2) + (z - cz).pow(2) <= radius * radius
+                });
+                // Leave some random holes too.
+                if in_some_ball && rng.chance(0.9) {
+                    output += &format!("{x},{y},{z}\n");
+                }
+            }
+        }
+    }
+    if output.is_empty() {
+        output += "0,0,0\n";
+    }
+    output
+}
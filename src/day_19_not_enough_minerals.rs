@@ -1,3 +1,6 @@
+use crate::branch_and_bound::{self, Config, Order, Problem, Solution};
+use crate::rng::Rng;
+
 pub fn run(input: &str) -> String {
     let blueprints: Vec<_> = input.lines().map(parse_blueprint).collect();
 
@@ -42,7 +45,7 @@ fn parse_blueprint(line: &str) -> Blueprint {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct State {
     ore: u32,
     clay: u32,
@@ -75,72 +78,83 @@ impl State {
     }
 }
 
-fn find_max_geodes(blueprint: &Blueprint, minutes: u32) -> u32 {
-    let mut max_geodes = 0;
-    let no_info = [false; 3];
-    let mut stack = vec![(State::new(minutes), no_info)];
-    while let Some((state, prev_minute_info)) = stack.pop() {
-        if state.remaining_minutes == 0 {
-            max_geodes = max_geodes.max(state.geodes);
-            continue;
-        }
+// The previous minute's info tells which robots could have been made on the previous minute but
+// weren't, in which case it doesn't make sense to make them now.
+type PrevMinuteInfo = [bool; 3];
+
+impl Problem for Blueprint {
+    type State = (State, PrevMinuteInfo);
+    type Key = ();
 
-        // If there are N remaining minutes, we could, at most, produce N geode robots, which would
-        // crack N-1 + N-2 + ... + 1 geodes. This is the same as the sum the N-1 first integers,
-        // which is (N-1)*N/2
-        let geode_upper_bound = state.geodes
-            + state.geode_robots * state.remaining_minutes
-            + (state.remaining_minutes - 1) * state.remaining_minutes / 2;
-        if geode_upper_bound <= max_geodes {
-            continue;
+    // The geodes we'll have by the end even if no more robots are made.
+    fn value(&self, (state, _): &Self::State) -> u64 {
+        (state.geodes + state.geode_robots * state.remaining_minutes) as u64
+    }
+
+    // If there are N remaining minutes, we could, at most, produce N geode robots, which would
+    // crack N-1 + N-2 + ... + 1 geodes. This is the same as the sum the N-1 first integers, which
+    // is (N-1)*N/2
+    fn upper_bound(&self, state_and_info: &Self::State) -> u64 {
+        let remaining_minutes = state_and_info.0.remaining_minutes as u64;
+        self.value(state_and_info) + remaining_minutes.saturating_sub(1) * remaining_minutes / 2
+    }
+
+    fn successors(
+        &self,
+        (state, prev_minute_info): &Self::State,
+        push: &mut impl FnMut(Self::State),
+    ) {
+        if state.remaining_minutes == 0 {
+            return;
         }
+        let no_info = [false; 3];
 
-        let can_make_geode_robot = state.ore >= blueprint.geode_robot_ore_cost
-            && state.obsidian >= blueprint.geode_robot_obsidian_cost;
+        let can_make_geode_robot = state.ore >= self.geode_robot_ore_cost
+            && state.obsidian >= self.geode_robot_obsidian_cost;
         if can_make_geode_robot {
             let mut new_state = state.tick();
             new_state.geode_robots += 1;
-            new_state.ore -= blueprint.geode_robot_ore_cost;
-            new_state.obsidian -= blueprint.geode_robot_obsidian_cost;
-            stack.push((new_state, no_info));
+            new_state.ore -= self.geode_robot_ore_cost;
+            new_state.obsidian -= self.geode_robot_obsidian_cost;
+            push((new_state, no_info));
 
             // If we can make a geode robot this turn, don't evaluate other possibilities. Making
             // geode robots is always best :)
-            continue;
+            return;
         }
 
         let [could_make_ore_robot, could_make_clay_robot, could_make_obsidian_robot] =
-            prev_minute_info;
+            *prev_minute_info;
 
-        let can_make_obsidian_robot = state.ore >= blueprint.obsidian_robot_ore_cost
-            && state.clay >= blueprint.obsidian_robot_clay_cost;
+        let can_make_obsidian_robot = state.ore >= self.obsidian_robot_ore_cost
+            && state.clay >= self.obsidian_robot_clay_cost;
         if can_make_obsidian_robot && !could_make_obsidian_robot {
             let mut new_state = state.tick();
             new_state.obsidian_robots += 1;
-            new_state.ore -= blueprint.obsidian_robot_ore_cost;
-            new_state.clay -= blueprint.obsidian_robot_clay_cost;
-            stack.push((new_state, no_info));
+            new_state.ore -= self.obsidian_robot_ore_cost;
+            new_state.clay -= self.obsidian_robot_clay_cost;
+            push((new_state, no_info));
         }
 
-        let can_make_clay_robot = state.ore >= blueprint.clay_robot_ore_cost;
-        let enough_clay_robots = state.clay_robots >= blueprint.obsidian_robot_clay_cost;
+        let can_make_clay_robot = state.ore >= self.clay_robot_ore_cost;
+        let enough_clay_robots = state.clay_robots >= self.obsidian_robot_clay_cost;
         if can_make_clay_robot && !could_make_clay_robot && !enough_clay_robots {
             let mut new_state = state.tick();
             new_state.clay_robots += 1;
-            new_state.ore -= blueprint.clay_robot_ore_cost;
-            stack.push((new_state, no_info));
+            new_state.ore -= self.clay_robot_ore_cost;
+            push((new_state, no_info));
         }
 
-        let can_make_ore_robot = state.ore >= blueprint.ore_robot_ore_cost;
-        let enough_ore_robots = state.ore_robots >= blueprint.ore_robot_ore_cost
-            && state.ore_robots >= blueprint.clay_robot_ore_cost
-            && state.ore_robots >= blueprint.obsidian_robot_ore_cost
-            && state.ore_robots >= blueprint.geode_robot_ore_cost;
+        let can_make_ore_robot = state.ore >= self.ore_robot_ore_cost;
+        let enough_ore_robots = state.ore_robots >= self.ore_robot_ore_cost
+            && state.ore_robots >= self.clay_robot_ore_cost
+            && state.ore_robots >= self.obsidian_robot_ore_cost
+            && state.ore_robots >= self.geode_robot_ore_cost;
         if can_make_ore_robot && !could_make_ore_robot && !enough_ore_robots {
             let mut new_state = state.tick();
-            new_state.ore -= blueprint.ore_robot_ore_cost;
+            new_state.ore -= self.ore_robot_ore_cost;
             new_state.ore_robots += 1;
-            stack.push((new_state, no_info));
+            push((new_state, no_info));
         }
 
         let minute_info = [
@@ -148,7 +162,76 @@ fn find_max_geodes(blueprint: &Blueprint, minutes: u32) -> u32 {
             can_make_clay_robot,
             can_make_obsidian_robot,
         ];
-        stack.push((state.tick(), minute_info))
+        push((state.tick(), minute_info))
+    }
+}
+
+fn find_max_geodes(blueprint: &Blueprint, minutes: u32) -> u32 {
+    search_max_geodes(blueprint, minutes, Config::default()).value as u32
+}
+
+fn search_max_geodes(
+    blueprint: &Blueprint,
+    minutes: u32,
+    config: Config,
+) -> Solution<(State, PrevMinuteInfo)> {
+    branch_and_bound::maximize(blueprint, (State::new(minutes), [false; 3]), config)
+}
+
+// Reports how many states each search strategy explores for every blueprint.
+pub fn search_stats(input: &str) -> String {
+    let blueprints: Vec<_> = input.lines().map(parse_blueprint).collect();
+    let configs = [
+        ("depth-first", Config::default()),
+        (
+            "best-first",
+            Config {
+                order: Order::BestFirst,
+                ..Config::default()
+            },
+        ),
+        (
+            "parallel depth-first",
+            Config {
+                parallel: true,
+                ..Config::default()
+            },
+        ),
+    ];
+
+    let mut lines = vec![];
+    for (minutes, blueprints) in [
+        (24, &blueprints[..]),
+        (32, &blueprints[..3.min(blueprints.len())]),
+    ] {
+        for blueprint in blueprints {
+            for (config_name, config) in configs {
+                let solution = search_max_geodes(blueprint, minutes, config);
+                lines.push(format!(
+                    "blueprint {} ({minutes} minutes), {config_name}: {} geodes; {}",
+                    blueprint.id, solution.value, solution.stats
+                ));
+            }
+        }
     }
-    max_geodes
+    lines.join("\n")
+}
+
+// Generates `size` blueprints, with robot costs in the same ranges as the puzzle input.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    (1..=size.max(1))
+        .map(|id| {
+            format!(
+                "Blueprint {id}: Each ore robot costs {} ore. Each clay robot costs {} ore. \
+                Each obsidian robot costs {} ore and {} clay. \
+                Each geode robot costs {} ore and {} obsidian.\n",
+                rng.range(2..=4),
+                rng.range(2..=4),
+                rng.range(2..=4),
+                rng.range(4..=20),
+                rng.range(2..=4),
+                rng.range(7..=20),
+            )
+        })
+        .collect()
 }
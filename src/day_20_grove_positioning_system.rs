@@ -1,3 +1,5 @@
+use crate::rng::Rng;
+
 pub fn run(input: &str) -> String {
     let numbers: Vec<i64> = input
         .lines()
@@ -6,10 +8,15 @@ pub fn run(input: &str) -> String {
 
     let decrypted_numbers: Vec<i64> = numbers.iter().map(|n| *n * 811589153).collect();
 
-    format!("{} {}", mix(&numbers, 1), mix(&decrypted_numbers, 10))
+    format!(
+        "{} {}",
+        grove_coordinates_sum(&mix(&numbers, 1)),
+        grove_coordinates_sum(&mix(&decrypted_numbers, 10))
+    )
 }
 
-fn mix(numbers: &[i64], mix_count: usize) -> i64 {
+// Returns the mixed numbers, starting from the first one of the original list.
+fn mix(numbers: &[i64], mix_count: usize) -> Vec<i64> {
     let len = numbers.len();
     // Encode a double-linked circular list as two arrays of previous and next number IDs.
     // Use a smaller integer for number IDs so that the vectors are move cache-friendly.
@@ -57,16 +64,64 @@ fn mix(numbers: &[i64], mix_count: usize) -> i64 {
         }
     }
 
-    let zero_id = numbers
+    let mut id = 0;
+    (0..len)
+        .map(|_| {
+            let num = numbers[id];
+            id = nexts[id] as usize;
+            num
+        })
+        .collect()
+}
+
+fn grove_coordinates_sum(mixed_numbers: &[i64]) -> i64 {
+    let zero_index = mixed_numbers
         .iter()
         .position(|n| *n == 0)
         .expect("0 must be on the list");
 
     [1000, 2000, 3000]
         .into_iter()
-        .map(|offset| {
-            let id = (0..offset).fold(zero_id, |id, _| nexts[id] as usize);
-            numbers[id]
-        })
+        .map(|offset| mixed_numbers[(zero_index + offset) % mixed_numbers.len()])
         .sum()
 }
+
+// Generates `size` numbers, exactly one of which is 0. Numbers can be repeated otherwise.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    // Number IDs are stored as u16.
+    let len = size.clamp(2, u16::MAX as usize);
+    let zero_index = rng.below(len);
+    (0..len)
+        .map(|i| {
+            let num = if i == zero_index {
+                0
+            } else {
+                let magnitude = rng.range(1..=10_000);
+                if rng.chance(0.5) {
+                    magnitude
+                } else {
+                    -magnitude
+                }
+            };
+            format!("{num}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, vecs};
+
+    #[test]
+    fn mix_preserves_numbers() {
+        let numbers = vecs(ints(-1_000_000..=1_000_000), 2..=50);
+        check(&(numbers, ints(1..=10)), |(numbers, mix_count)| {
+            let mut mixed = mix(numbers, *mix_count as usize);
+            let mut numbers = numbers.clone();
+            mixed.sort();
+            numbers.sort();
+            mixed == numbers
+        });
+    }
+}
@@ -1,4 +1,8 @@
-use std::{collections::HashMap, fmt};
+use crate::rng::Rng;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 pub fn run(input: &str) -> String {
     let monkeys: HashMap<_, _> = input.lines().map(parse_monkey).collect();
@@ -132,3 +136,139 @@ fn solve_for(var_id: MonkeyId, monkeys: &HashMap<MonkeyId, MonkeyJob>) -> i64 {
         }
     }
 }
+
+// Generates a tree of about `size` monkeys. `humn` is used only once, on a chain of operations up
+// to `root`, and all divisions are exact, both with the `humn` number on the input and with the
+// one that makes both sides of `root` equal.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let mut generator = MonkeyGenerator {
+        rng,
+        used_ids: HashSet::from(["root".to_string(), "humn".to_string()]),
+        jobs: vec![],
+    };
+    let humn_number = generator.rng.range(1..=5000);
+    let humn_solution = generator.rng.range(1..=5000);
+    generator
+        .jobs
+        .push(("humn".to_string(), humn_number.to_string()));
+
+    // Values of the current node on the chain with both humn numbers.
+    let (mut value, mut solution_value) = (humn_number, humn_solution);
+    let mut node = "humn".to_string();
+    let chain_len = (size / 8).max(1);
+    let budget_per_step = size / 2 / chain_len;
+    for _ in 0..chain_len {
+        let small_divisor = (2..=9).find(|d| value % d == 0 && solution_value % d == 0);
+        let big_values = value.abs().max(solution_value.abs()) > 1_000_000_000;
+        let (op, constant, node_on_left) = match generator.rng.below(5) {
+            0 if !big_values => ('*', generator.rng.range(2..=9), generator.rng.chance(0.5)),
+            1 if small_divisor.is_some() => ('/', small_divisor.unwrap(), true),
+            2 => (
+                '-',
+                generator.rng.range(1..=1000),
+                generator.rng.chance(0.5),
+            ),
+            _ => (
+                '+',
+                generator.rng.range(1..=1000),
+                generator.rng.chance(0.5),
+            ),
+        };
+        let apply = |v: i64| match (op, node_on_left) {
+            ('*', _) => v * constant,
+            ('/', _) => v / constant,
+            ('-', true) => v - constant,
+            ('-', false) => constant - v,
+            _ => v + constant,
+        };
+        (value, solution_value) = (apply(value), apply(solution_value));
+
+        let constant_node = generator.constant(constant, budget_per_step);
+        let (lhs, rhs) = if node_on_left {
+            (node, constant_node)
+        } else {
+            (constant_node, node)
+        };
+        node = generator.new_id();
+        generator
+            .jobs
+            .push((node.clone(), format!("{lhs} {op} {rhs}")));
+    }
+
+    let other_side = generator.constant(solution_value, size / 2);
+    let (lhs, rhs) = if generator.rng.chance(0.5) {
+        (node, other_side)
+    } else {
+        (other_side, node)
+    };
+    generator
+        .jobs
+        .push(("root".to_string(), format!("{lhs} + {rhs}")));
+
+    let mut lines: Vec<_> = generator
+        .jobs
+        .iter()
+        .map(|(id, job)| format!("{id}: {job}\n"))
+        .collect();
+    generator.rng.shuffle(&mut lines);
+    lines.concat()
+}
+
+struct MonkeyGenerator<'a> {
+    rng: &'a mut Rng,
+    used_ids: HashSet<String>,
+    jobs: Vec<(String, String)>,
+}
+
+impl MonkeyGenerator<'_> {
+    fn new_id(&mut self) -> String {
+        loop {
+            let id: String = (0..4)
+                .map(|_| (b'a' + self.rng.below(26) as u8) as char)
+                .collect();
+            if self.used_ids.insert(id.clone()) {
+                return id;
+            }
+        }
+    }
+
+    // Adds monkeys that yell the given value in the end, using up to `budget` more monkeys, and
+    // returns the ID of the top one. Monkeys that yell numbers only yell positive ones.
+    fn constant(&mut self, value: i64, budget: usize) -> String {
+        let id = self.new_id();
+        if value >= 0 && (budget < 2 || (value <= 20 && self.rng.chance(0.3))) {
+            self.jobs.push((id.clone(), value.to_string()));
+            return id;
+        }
+
+        let budget = budget.saturating_sub(2);
+        let lhs_budget = self.rng.below(budget + 1);
+        let small_divisor = (2..=9).find(|d| value != 0 && value % d == 0);
+        let (lhs_value, op, rhs_value) = match self.rng.below(4) {
+            0 if small_divisor.is_some() => {
+                let divisor = small_divisor.unwrap();
+                (value / divisor, '*', divisor)
+            }
+            1 if value.abs() < 1_000_000_000 => {
+                let divisor = self.rng.range(2..=5);
+                (value * divisor, '/', divisor)
+            }
+            2 if value > 1 => {
+                let lhs_value = self.rng.range(1..=value - 1);
+                (lhs_value, '+', value - lhs_value)
+            }
+            _ if value < 0 => {
+                let lhs_value = self.rng.range(1..=100);
+                (lhs_value, '-', lhs_value - value)
+            }
+            _ => {
+                let rhs_value = self.rng.range(1..=100);
+                (value + rhs_value, '-', rhs_value)
+            }
+        };
+        let lhs = self.constant(lhs_value, lhs_budget);
+        let rhs = self.constant(rhs_value, budget - lhs_budget);
+        self.jobs.push((id.clone(), format!("{lhs} {op} {rhs}")));
+        id
+    }
+}
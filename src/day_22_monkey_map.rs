@@ -1,3 +1,4 @@
+use crate::rng::Rng;
 use std::collections::VecDeque;
 
 pub fn run(input: &str) -> String {
@@ -5,7 +6,8 @@ pub fn run(input: &str) -> String {
         .split_once("\n\n")
         .expect("input should have two parts");
     let map = parse_map(map_part);
-    let instructions = parse_instructions(inst_part);
+    let instructions =
+        parse_instructions(inst_part).unwrap_or_else(|err| panic!("invalid instructions: {err}"));
 
     let ans_1 = get_password(&map, &instructions, wrap_around_2d);
     let ans_2 = get_password(&map, &instructions, wrap_around_3d_cube);
@@ -13,6 +15,12 @@ pub fn run(input: &str) -> String {
     format!("{ans_1} {ans_2}")
 }
 
+// Entry point for fuzzing the instructions parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    let (_, instructions_section) = input.split_once("\n\n").unwrap_or(("", input));
+    let _ = parse_instructions(instructions_section);
+}
+
 #[derive(Copy, Clone, PartialEq)]
 enum Tile {
     Empty,
@@ -227,19 +235,95 @@ fn parse_map(s: &str) -> Map {
         .collect()
 }
 
-fn parse_instructions(s: &str) -> Vec<Instruction> {
+fn parse_instructions(s: &str) -> Result<Vec<Instruction>, String> {
     s.replace('L', " L ")
         .replace('R', " R ")
         .split_whitespace()
         .map(|token| {
             if let Ok(num) = token.parse() {
-                return Advance(num);
+                return Ok(Advance(num));
             }
             match token {
-                "L" => TurnLeft,
-                "R" => TurnRight,
-                _ => unreachable!("unexpected instruction '{token}'"),
+                "L" => Ok(TurnLeft),
+                "R" => Ok(TurnRight),
+                _ => Err(format!("unexpected instruction '{token}'")),
             }
         })
         .collect()
 }
+
+// The 11 different nets of a cube, not counting rotations and reflections.
+const CUBE_NETS: [&str; 11] = [
+    "#...\n####\n#...",
+    "#...\n####\n.#..",
+    "#...\n####\n..#.",
+    "#...\n####\n...#",
+    ".#..\n####\n.#..",
+    ".#..\n####\n..#.",
+    "##..\n.###\n.#..",
+    "##..\n.###\n..#.",
+    "##..\n.###\n...#",
+    "##..\n.##.\n..##",
+    "###..\n..###",
+];
+
+// Generates a map on a randomly rotated and reflected cube net and a path of `size` moves. With a
+// `size` under 50 the cube faces are 4x4 like on the sample, otherwise they are 50x50 like on the
+// puzzle input.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let cube_size = if size < 50 { 4 } else { 50 };
+    let mut faces: Vec<Point> = rng
+        .choose(&CUBE_NETS)
+        .lines()
+        .enumerate()
+        .flat_map(|(y, line)| {
+            line.chars()
+                .enumerate()
+                .filter(|&(_x, ch)| ch == '#')
+                .map(move |(x, _ch)| (x as i32, y as i32))
+        })
+        .collect();
+    for _ in 0..rng.below(4) {
+        faces = faces.into_iter().map(turn_right).collect();
+    }
+    if rng.chance(0.5) {
+        faces = faces.into_iter().map(|(x, y)| (-x, y)).collect();
+    }
+    let min_x = faces.iter().map(|f| f.0).min().unwrap();
+    let min_y = faces.iter().map(|f| f.1).min().unwrap();
+    let faces: Vec<_> = faces.iter().map(|&(x, y)| (x - min_x, y - min_y)).collect();
+
+    let map_height = (faces.iter().map(|f| f.1).max().unwrap() + 1) * cube_size;
+    let map_width = (faces.iter().map(|f| f.0).max().unwrap() + 1) * cube_size;
+    let mut output = String::new();
+    for y in 0..map_height {
+        let mut line: String = (0..map_width)
+            .map(|x| {
+                if !faces.contains(&(x / cube_size, y / cube_size)) {
+                    ' '
+                } else if rng.chance(0.1) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        if y == 0 && !line.contains('.') {
+            // The path starts on the first open tile of the first row.
+            let first_tile = line.find('#').unwrap();
+            line.replace_range(first_tile..=first_tile, ".");
+        }
+        output += line.trim_end();
+        output.push('\n');
+    }
+
+    output.push('\n');
+    for i in 0..size.max(1) {
+        if i > 0 {
+            output.push(*rng.choose(&['L', 'R']));
+        }
+        output += &rng.range(1..=3 * cube_size as i64).to_string();
+    }
+    output.push('\n');
+    output
+}
@@ -1,3 +1,4 @@
+use crate::rng::Rng;
 use rustc_hash::FxHashMap as HashMap;
 
 pub fn run(input: &str) -> String {
@@ -105,3 +106,17 @@ fn parse_input(input: &str) -> (Vec<(usize, usize)>, Map) {
 
     (elves, map)
 }
+
+// Generates a `size` x `size` grove with elves on a random part of the tiles.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let size = size.max(1);
+    let density = rng.range(20..=80) as f64 / 100.0;
+    (0..size)
+        .map(|_| {
+            let row: String = (0..size)
+                .map(|_| if rng.chance(density) { '#' } else { '.' })
+                .collect();
+            row + "\n"
+        })
+        .collect()
+}
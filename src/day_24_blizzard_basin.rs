@@ -1,4 +1,8 @@
-use crate::dijkstra::shortest_path;
+use crate::dijkstra::{
+    astar_weighted_with_limits, astar_weighted_with_route, shortest_path_indexed, Limits, Outcome,
+};
+use crate::rng::Rng;
+use std::time::Instant;
 
 pub fn run(input: &str) -> String {
     let (map, start, end) = parse_map(input);
@@ -9,24 +13,186 @@ pub fn run(input: &str) -> String {
     format!("{first_trip_time} {back_to_end_time}")
 }
 
+// Alternative entry point that shows the moves taken on each trip, like in the puzzle description.
+pub fn trace(input: &str) -> String {
+    let (map, start, end) = parse_map(input);
+    let map = &map;
+    let (node_count, _) = state_indexing(map);
+    let mut trips = vec![];
+    let mut time = 0;
+    for (trip, (from, to)) in [(start, end), (end, start), (start, end)]
+        .into_iter()
+        .enumerate()
+    {
+        let trip = trip + 1;
+        let unit_cost_moves = |&state: &_| next_moves(map, from, to, state).map(|s| (s, 1));
+        // As in `search_stats`, this guarantees the search ends even if the end is unreachable.
+        let limits = Limits {
+            max_distance: Some(node_count),
+            ..Limits::default()
+        };
+        let (outcome, _) = astar_weighted_with_route(
+            &(from, time),
+            |&(pos, _t)| pos == to,
+            unit_cost_moves,
+            |&(pos, _t)| manhattan_distance(pos, to),
+            &limits,
+        );
+        let Outcome::Found((dist, route)) = outcome else {
+            trips.push(format!("Trip {trip}: no path found"));
+            break;
+        };
+        trips.push(format!(
+            "Trip {trip}, {dist} minutes:\n{}",
+            describe_moves(&route)
+        ));
+        time += dist as i32;
+    }
+    trips.join("\n\n")
+}
+
+// Reports how many states are expanded by A* compared to plain Dijkstra's on each trip, and how
+// long they take compared to the dense BFS used by the solution.
+pub fn search_stats(input: &str) -> String {
+    let (map, start, end) = parse_map(input);
+    let map = &map;
+    let (node_count, state_index) = state_indexing(map);
+    let mut lines = vec![];
+    let mut time = 0;
+    for (trip, (from, to)) in [(start, end), (end, start), (start, end)]
+        .into_iter()
+        .enumerate()
+    {
+        let trip = trip + 1;
+        let next_moves = |&state: &_| next_moves(map, from, to, state);
+        let unit_cost_moves = |state: &_| next_moves(state).map(|s| (s, 1));
+        let is_end = |&(pos, _t): &_| pos == to;
+        for (name, use_heuristic) in [("Dijkstra", false), ("A*", true)] {
+            let heuristic = |&(pos, _t): &_| {
+                if use_heuristic {
+                    manhattan_distance(pos, to)
+                } else {
+                    0
+                }
+            };
+            // No shortest path can be longer than the number of distinct states, so this guarantees
+            // the search ends even if the end is unreachable.
+            let limits = Limits {
+                max_distance: Some(node_count),
+                ..Limits::default()
+            };
+            let instant = Instant::now();
+            let (outcome, stats) = astar_weighted_with_limits(
+                &(from, time),
+                is_end,
+                unit_cost_moves,
+                heuristic,
+                &limits,
+            );
+            let Outcome::Found(dist) = outcome else {
+                panic!("there must be a path from start to end")
+            };
+            lines.push(format!(
+                "trip {trip}, {name}: {dist} minutes; {stats} ({:.1?})",
+                instant.elapsed()
+            ));
+        }
+        let instant = Instant::now();
+        let dist =
+            shortest_path_indexed(&(from, time), is_end, next_moves, &state_index, node_count)
+                .expect("there must be a path from start to end");
+        lines.push(format!(
+            "trip {trip}, dense BFS: {dist} minutes ({:.1?})",
+            instant.elapsed()
+        ));
+        time += dist as i32;
+    }
+    lines.join("\n")
+}
+
 // `start` and `end` can be off-bounds by one.
 fn shortest_travel_time(map: &Map, start: Point, end: Point, start_time: i32) -> i32 {
-    let next_moves = |&((x, y), t): &_| {
-        // Note: include current (x, y) as a possible move for waiting a turn.
-        [(x + 1, y), (x, y + 1), (x, y), (x, y - 1), (x - 1, y)]
-            .into_iter()
-            .filter(move |&(x, y)| {
-                let start_or_end = (x, y) == start || (x, y) == end;
-                let in_bounds = x >= 0 && x < map[0].len() as i32 && y >= 0 && y < map.len() as i32;
-                start_or_end || (in_bounds && tile_is_empty_at(map, x, y, t + 1))
-            })
-            .map(move |pos| (pos, t + 1))
-    };
-    let dist = shortest_path(&(start, start_time), |&(pos, _t)| pos == end, next_moves)
-        .expect("there must be a path from start to end");
+    let next_moves = |&state: &_| next_moves(map, start, end, state);
+    let is_end = |&(pos, _t): &_| pos == end;
+    let (node_count, state_index) = state_indexing(map);
+    let dist = shortest_path_indexed(
+        &(start, start_time),
+        is_end,
+        next_moves,
+        state_index,
+        node_count,
+    )
+    .expect("there must be a path from start to end");
     dist as i32 + start_time
 }
 
+fn next_moves(
+    map: &Map,
+    start: Point,
+    end: Point,
+    ((x, y), t): (Point, i32),
+) -> impl Iterator<Item = (Point, i32)> + '_ {
+    // Note: include current (x, y) as a possible move for waiting a turn.
+    [(x + 1, y), (x, y + 1), (x, y), (x, y - 1), (x - 1, y)]
+        .into_iter()
+        .filter(move |&(x, y)| {
+            let start_or_end = (x, y) == start || (x, y) == end;
+            let in_bounds = x >= 0 && x < map[0].len() as i32 && y >= 0 && y < map.len() as i32;
+            start_or_end || (in_bounds && tile_is_empty_at(map, x, y, t + 1))
+        })
+        .map(move |pos| (pos, t + 1))
+}
+
+// Maps each (position, time) state to a contiguous index. Blizzards come back to the same positions
+// every lcm(width, height) minutes, so states on the same position and at the same point of that
+// cycle are equivalent.
+fn state_indexing(map: &Map) -> (usize, impl Fn(&(Point, i32)) -> usize) {
+    let width = map[0].len();
+    let height = map.len();
+    let cycle_len = lcm(width, height);
+    // Positions include the tiles above and below the map, where the start and end are.
+    let position_count = width * (height + 2);
+    let state_index = move |&((x, y), t): &(Point, i32)| {
+        let position_index = (y + 1) as usize * width + x as usize;
+        (t as usize % cycle_len) * position_count + position_index
+    };
+    (cycle_len * position_count, state_index)
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let gcd = |mut a: usize, mut b: usize| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
+
+fn manhattan_distance((x1, y1): Point, (x2, y2): Point) -> usize {
+    x1.abs_diff(x2) as usize + y1.abs_diff(y2) as usize
+}
+
+// Describes the moves of a route in the same format as the puzzle description.
+fn describe_moves(route: &[(Point, i32)]) -> String {
+    let mut lines = vec![];
+    for window in route.windows(2) {
+        let [((x, y), _t), ((next_x, next_y), next_t)] = window else {
+            unreachable!()
+        };
+        let action = match (next_x - x, next_y - y) {
+            (0, 0) => "wait",
+            (1, 0) => "move right",
+            (-1, 0) => "move left",
+            (0, 1) => "move down",
+            (0, -1) => "move up",
+            _ => unreachable!("moves should be to adjacent tiles"),
+        };
+        lines.push(format!("Minute {next_t}, {action}."));
+    }
+    lines.join("\n")
+}
+
 // Determines whether an (x, y) tile has no blizzards at a given time. x and y must be in-bounds.
 fn tile_is_empty_at(map: &Map, x: i32, y: i32, time: i32) -> bool {
     let width = map[0].len() as i32;
@@ -97,3 +263,84 @@ fn parse_map(input: &str) -> (Map, Point, Point) {
 
     (map, start, end)
 }
+
+// Generates a valley `size` tiles wide, with blizzards on about a quarter of its tiles. Like on
+// the puzzle input, no blizzards go up or down on the start and end columns. Tries again until
+// there's a way to go to the end, back to the start, and to the end again.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    let width = size.max(2);
+    let height = (width / 4).max(2);
+    loop {
+        let mut output = format!("#.{}\n", "#".repeat(width));
+        for _ in 0..height {
+            output.push('#');
+            for x in 0..width {
+                let vertical_allowed = x != 0 && x != width - 1;
+                output.push(match rng.below(16) {
+                    0 => '<',
+                    1 => '>',
+                    2 if vertical_allowed => '^',
+                    3 if vertical_allowed => 'v',
+                    _ => '.',
+                });
+            }
+            output += "#\n";
+        }
+        output += &format!("{}.#\n", "#".repeat(width));
+
+        let (map, start, end) = parse_map(&output);
+        let (node_count, state_index) = state_indexing(&map);
+        let travel_time = |from: Point, to: Point, start_time: i32| {
+            let next_moves = |&state: &_| next_moves(&map, from, to, state);
+            let is_end = |&(pos, _t): &_| pos == to;
+            let dist = shortest_path_indexed(
+                &(from, start_time),
+                is_end,
+                next_moves,
+                &state_index,
+                node_count,
+            )?;
+            Some(dist as i32 + start_time)
+        };
+        let all_trips_possible = travel_time(start, end, 0)
+            .and_then(|t| travel_time(end, start, t))
+            .and_then(|t| travel_time(start, end, t))
+            .is_some();
+        if all_trips_possible {
+            return output;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_shows_every_trip() {
+        let sample = std::fs::read_to_string("samples/24.txt").unwrap();
+        let (input, _expected) = sample.split_once("\n~~~\n").unwrap();
+        let trace = trace(input);
+        let trip_minutes: Vec<_> = trace
+            .lines()
+            .filter(|line| line.starts_with("Trip"))
+            .collect();
+        assert_eq!(
+            trip_minutes,
+            [
+                "Trip 1, 18 minutes:",
+                "Trip 2, 23 minutes:",
+                "Trip 3, 13 minutes:"
+            ]
+        );
+        let moves = trace.lines().filter(|line| line.starts_with("Minute"));
+        assert_eq!(moves.count(), 18 + 23 + 13);
+        assert!(trace.ends_with("Minute 54, move down."));
+    }
+
+    #[test]
+    fn trace_reports_unreachable_ends() {
+        // The only tile of the valley always has a blizzard on it.
+        assert_eq!(trace("#.#\n#^#\n#.#"), "Trip 1: no path found");
+    }
+}
@@ -1,24 +1,43 @@
+use crate::rng::Rng;
+use crate::streaming;
+use std::io::BufRead;
+
 pub fn run(input: &str) -> String {
-    let total = input.lines().map(parse_snafu).sum();
+    run_streaming(&mut input.as_bytes())
+}
+
+pub fn run_streaming(input: &mut dyn BufRead) -> String {
+    let total = streaming::lines(input)
+        .map(|line| parse_snafu(&line).unwrap_or_else(|err| panic!("{err}")))
+        .sum();
     num_to_snafu(total)
 }
 
-fn parse_snafu(s: &str) -> i64 {
-    let mut res = 0;
-    let mut mul = 1;
-    for ch in s.chars().rev() {
+// Entry point for fuzzing the SNAFU numbers parser; see fuzz.rs.
+pub fn fuzz_target(input: &str) {
+    for line in input.lines() {
+        let _ = parse_snafu(line);
+    }
+}
+
+// Reads the digits from the most significant one, so leading zeros can't overflow anything.
+fn parse_snafu(s: &str) -> Result<i64, String> {
+    let mut res: i64 = 0;
+    for ch in s.chars() {
         let digit = match ch {
             '2' => 2,
             '1' => 1,
             '0' => 0,
             '-' => -1,
             '=' => -2,
-            _ => unreachable!("invalid SNAFU digit '{ch}'"),
+            _ => return Err(format!("invalid SNAFU digit '{ch}'")),
         };
-        res += mul * digit;
-        mul *= 5;
+        res = res
+            .checked_mul(5)
+            .and_then(|res| res.checked_add(digit))
+            .ok_or_else(|| format!("SNAFU number {s} doesn't fit in 64 bits"))?;
     }
-    res
+    Ok(res)
 }
 
 fn num_to_snafu(n: i64) -> String {
@@ -42,3 +61,46 @@ fn num_to_snafu(n: i64) -> String {
     }
     digits.into_iter().rev().collect()
 }
+
+// Generates `size` SNAFU numbers of different lengths.
+pub fn generate_input(rng: &mut Rng, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            let max = 5i64.pow(rng.range(1..=15) as u32);
+            num_to_snafu(rng.range(1..=max)) + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, strings};
+
+    #[test]
+    fn snafu_round_trip() {
+        check(&ints(0..=1_000_000_000_000_000), |&n| {
+            parse_snafu(&num_to_snafu(n)) == Ok(n)
+        });
+    }
+
+    // Different SNAFU strings can mean the same number, like "01" and "1", but converting them back
+    // should always give the canonical one. Strings whose first non-zero digit is 1 or 2 are
+    // non-negative, as that digit outweighs all the ones after it. They have up to 25 digits, so
+    // that they fit in an i64.
+    #[test]
+    fn snafu_strings_round_trip() {
+        let parts = (
+            strings("0", 0..=3),
+            strings("12", 1..=1),
+            strings("0=-12", 0..=21),
+        );
+        check(&parts, |(zeros, first_digit, rest)| {
+            let canonical = format!("{first_digit}{rest}");
+            num_to_snafu(parse_snafu(&format!("{zeros}{canonical}")).unwrap()) == canonical
+        });
+        assert_eq!(num_to_snafu(parse_snafu("000").unwrap()), "0");
+        assert!(parse_snafu("2".repeat(28).as_str()).is_err());
+        assert!(parse_snafu("12x").is_err());
+    }
+}
@@ -1,12 +1,21 @@
+// Some of the simpler entry points aren't used by the puzzles, which need the stats, limits or
+// routes of the fuller variants instead. They're marked with allow(dead_code), and tested below.
+
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
 use std::hash::Hash;
+use std::ops::Add;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::time::Instant;
 
 use rustc_hash::FxHashMap as HashMap;
 
 // Calculates the shortest path distance between a given start node and a goal using Dijkstra's
 // algorithm. The goal is given as a predicate function instead of a node so that the caller can
-// determine when it is reached.
+// determine when it is reached. All edges have a cost of 1; see `shortest_path_weighted` for
+// graphs with other edge costs.
+#[allow(dead_code)]
 pub fn shortest_path<T, PT, FT, IT>(start: &T, is_goal: PT, successors: FT) -> Option<usize>
 where
     T: Eq + Hash + Clone,
@@ -14,20 +23,799 @@ where
     FT: Fn(&T) -> IT,
     IT: IntoIterator<Item = T>,
 {
+    let weighted_successors = |node: &T| successors(node).into_iter().map(|succ| (succ, 1));
+    shortest_path_weighted(start, is_goal, weighted_successors)
+}
+
+// Like `shortest_path`, but the successors function returns (node, cost) pairs. Costs must not be
+// negative, as Dijkstra's algorithm doesn't work with negative edges; debug builds check this.
+#[allow(dead_code)]
+pub fn shortest_path_weighted<T, C, PT, FT, IT>(start: &T, is_goal: PT, successors: FT) -> Option<C>
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+{
+    astar_weighted(start, is_goal, successors, |_| C::ZERO)
+}
+
+// Calculates the shortest path distance using the A* algorithm, which is like Dijkstra's but
+// explores first the nodes that seem closer to the goal according to a heuristic function. The
+// heuristic must never overestimate the distance to the goal, or else the returned distance might
+// not be the shortest one. Debug builds check that the heuristic is consistent, which is a bit
+// stronger: its estimate must not drop by more than the cost of the edge taken.
+#[allow(dead_code)]
+pub fn astar<T, PT, FT, IT, HT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    heuristic: HT,
+) -> Option<usize>
+where
+    T: Eq + Hash + Clone,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+    HT: Fn(&T) -> usize,
+{
+    let weighted_successors = |node: &T| successors(node).into_iter().map(|succ| (succ, 1));
+    astar_weighted(start, is_goal, weighted_successors, heuristic)
+}
+
+#[allow(dead_code)]
+pub fn astar_weighted<T, C, PT, FT, IT, HT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    heuristic: HT,
+) -> Option<C>
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    HT: Fn(&T) -> C,
+{
+    astar_weighted_with_stats(start, is_goal, successors, heuristic).0
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Stats {
+    // The number of nodes whose successors were explored.
+    pub expanded: usize,
+    // The number of nodes pushed into the priority queue, including the start node.
+    pub pushed: usize,
+    // The number of nodes popped from the priority queue after a shorter path to them had already
+    // been found.
+    pub stale_popped: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expanded {}, pushed {}, stale popped {}",
+            self.expanded, self.pushed, self.stale_popped
+        )
+    }
+}
+
+// Like `astar_weighted`, but also returns some stats about the search. Useful for comparing
+// heuristics, or comparing against plain Dijkstra's by using a heuristic that always returns 0.
+pub fn astar_weighted_with_stats<T, C, PT, FT, IT, HT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    heuristic: HT,
+) -> (Option<C>, Stats)
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    HT: Fn(&T) -> C,
+{
+    let (outcome, stats) =
+        astar_weighted_with_limits(start, is_goal, successors, heuristic, &Limits::default());
+    match outcome {
+        Outcome::Found(distance) => (Some(distance), stats),
+        Outcome::Unreachable => (None, stats),
+        Outcome::LimitReached => unreachable!("there are no limits"),
+    }
+}
+
+// Limits to stop searches that could take too long, or never end at all, like when the goal is not
+// reachable on an infinite graph.
+pub struct Limits<'a, C> {
+    pub max_expanded: Option<usize>,
+    // Stop once it's known that the goal is further than this distance.
+    pub max_distance: Option<C>,
+    pub deadline: Option<Instant>,
+    // Stop when this flag is set, e.g. from another thread.
+    pub cancel: Option<&'a AtomicBool>,
+}
+
+// Implemented manually, as the derived Default would require C: Default.
+impl<C> Default for Limits<'_, C> {
+    fn default() -> Self {
+        Limits {
+            max_expanded: None,
+            max_distance: None,
+            deadline: None,
+            cancel: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome<C> {
+    Found(C),
+    Unreachable,
+    LimitReached,
+}
+
+// The deadline and the cancel flag are only checked every this many expanded nodes, as checking
+// them is not free.
+const LIMITS_CHECK_INTERVAL: usize = 1024;
+
+// Like `astar_weighted_with_stats`, but the search stops early if any of the given limits is
+// reached.
+pub fn astar_weighted_with_limits<T, C, PT, FT, IT, HT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    heuristic: HT,
+    limits: &Limits<C>,
+) -> (Outcome<C>, Stats)
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    HT: Fn(&T) -> C,
+{
+    astar_weighted_visiting(start, is_goal, successors, heuristic, limits, &mut ())
+}
+
+// Hooks to observe what a search is doing, e.g. for debugging or visualization. All methods do
+// nothing by default, and () can be used as a visitor that observes nothing.
+pub trait Visitor<T, C> {
+    // A node is pushed into the priority queue, including the start node.
+    fn on_push(&mut self, _node: &T, _distance: C) {}
+    // A node is popped from the priority queue to be expanded. Stale nodes are not reported.
+    fn on_pop(&mut self, _node: &T, _distance: C) {}
+    fn on_goal(&mut self, _node: &T, _distance: C) {}
+    // A shorter path to a node was found, coming from another node.
+    fn on_relax(&mut self, _from: &T, _to: &T, _distance: C) {}
+}
+
+impl<T, C> Visitor<T, C> for () {}
+
+// Like `astar_weighted_with_limits`, but notifying a visitor of every step of the search.
+pub fn astar_weighted_visiting<T, C, PT, FT, IT, HT, V>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    heuristic: HT,
+    limits: &Limits<C>,
+    visitor: &mut V,
+) -> (Outcome<C>, Stats)
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    HT: Fn(&T) -> C,
+    V: Visitor<T, C>,
+{
+    let mut stats = Stats::default();
     let mut unvisited = BinaryHeap::new();
     let mut distances = HashMap::default();
 
+    distances.insert(start.clone(), C::ZERO);
+    unvisited.push(Node {
+        estimate: heuristic(start),
+        value: start.clone(),
+        distance: C::ZERO,
+    });
+    stats.pushed += 1;
+    visitor.on_push(start, C::ZERO);
+
+    while let Some(min_dist_node) = unvisited.pop() {
+        let Node {
+            value,
+            distance,
+            estimate,
+        } = min_dist_node;
+
+        // The estimate never overestimates, so if the closest node is already beyond the max
+        // distance, so is the goal.
+        if limits.max_distance.is_some_and(|max| estimate > max) {
+            return (Outcome::LimitReached, stats);
+        }
+
+        if is_goal(&value) {
+            debug_assert!(
+                heuristic(&value) == C::ZERO,
+                "heuristic should be zero at the goal"
+            );
+            visitor.on_goal(&value, distance);
+            return (Outcome::Found(distance), stats);
+        }
+
+        if distances[&value] < distance {
+            stats.stale_popped += 1;
+            continue;
+        }
+
+        if limits.max_expanded.is_some_and(|max| stats.expanded >= max) {
+            return (Outcome::LimitReached, stats);
+        }
+        if stats.expanded % LIMITS_CHECK_INTERVAL == 0 {
+            let past_deadline = limits.deadline.is_some_and(|d| Instant::now() >= d);
+            let cancelled = limits.cancel.is_some_and(|c| c.load(Relaxed));
+            if past_deadline || cancelled {
+                return (Outcome::LimitReached, stats);
+            }
+        }
+
+        stats.expanded += 1;
+        visitor.on_pop(&value, distance);
+        let node_estimate = heuristic(&value);
+        for (succ, cost) in successors(&value) {
+            debug_assert!(cost >= C::ZERO, "edge costs should not be negative");
+            let succ_estimate = heuristic(&succ);
+            // This checks that the heuristic is consistent (i.e. its estimate doesn't drop by more
+            // than the cost of any edge), not that it never overestimates. But a consistent
+            // heuristic that is zero at the goal never overestimates, and most heuristics that
+            // never overestimate are consistent too, like the Manhattan distance on a grid.
+            debug_assert!(
+                node_estimate <= cost + succ_estimate,
+                "heuristic should be consistent"
+            );
+            let path_dist = distance + cost;
+            if distances
+                .get(&succ)
+                .is_none_or(|&succ_dist| path_dist < succ_dist)
+            {
+                distances.insert(succ.clone(), path_dist);
+                visitor.on_relax(&value, &succ, path_dist);
+                visitor.on_push(&succ, path_dist);
+                unvisited.push(Node {
+                    value: succ,
+                    distance: path_dist,
+                    estimate: path_dist + succ_estimate,
+                });
+                stats.pushed += 1;
+            }
+        }
+    }
+    (Outcome::Unreachable, stats)
+}
+
+// Like `astar_weighted_with_limits`, but when the goal is found also returns the route from start
+// to goal, both included.
+pub fn astar_weighted_with_route<T, C, PT, FT, IT, HT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    heuristic: HT,
+    limits: &Limits<C>,
+) -> (Outcome<(C, Vec<T>)>, Stats)
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    HT: Fn(&T) -> C,
+{
+    // Remembers where each node was last reached from, which is on its shortest path once the node
+    // is settled.
+    struct Predecessors<T> {
+        predecessors: HashMap<T, T>,
+        goal: Option<T>,
+    }
+    impl<T: Eq + Hash + Clone, C> Visitor<T, C> for Predecessors<T> {
+        fn on_relax(&mut self, from: &T, to: &T, _distance: C) {
+            self.predecessors.insert(to.clone(), from.clone());
+        }
+        fn on_goal(&mut self, node: &T, _distance: C) {
+            self.goal = Some(node.clone());
+        }
+    }
+
+    let mut visitor = Predecessors {
+        predecessors: HashMap::default(),
+        goal: None,
+    };
+    let (outcome, stats) =
+        astar_weighted_visiting(start, is_goal, successors, heuristic, limits, &mut visitor);
+    let outcome = match outcome {
+        Outcome::Found(distance) => {
+            let mut route = vec![visitor.goal.unwrap()];
+            while let Some(pred) = visitor.predecessors.remove(route.last().unwrap()) {
+                route.push(pred);
+            }
+            route.reverse();
+            Outcome::Found((distance, route))
+        }
+        Outcome::Unreachable => Outcome::Unreachable,
+        Outcome::LimitReached => Outcome::LimitReached,
+    };
+    (outcome, stats)
+}
+
+// Calculates the shortest path distance between a start and a goal node by searching from both
+// ends at the same time, which usually explores far fewer nodes than searching from the start
+// only. The `predecessors` function must return the nodes that have an edge to a given node, i.e.
+// the successors on the reversed graph. All edges have a cost of 1.
+#[allow(dead_code)]
+pub fn bidirectional_shortest_path<T, FT, IT, PFT, PIT>(
+    start: &T,
+    goal: &T,
+    successors: FT,
+    predecessors: PFT,
+) -> Option<usize>
+where
+    T: Eq + Hash + Clone,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+    PFT: Fn(&T) -> PIT,
+    PIT: IntoIterator<Item = T>,
+{
+    bidirectional_shortest_path_weighted(
+        start,
+        goal,
+        |node: &T| successors(node).into_iter().map(|succ| (succ, 1)),
+        |node: &T| predecessors(node).into_iter().map(|pred| (pred, 1)),
+    )
+}
+
+// Like `bidirectional_shortest_path`, but both functions return (node, cost) pairs, where the cost
+// is the one of the edge between the given node and the returned one, in either direction.
+#[allow(dead_code)]
+pub fn bidirectional_shortest_path_weighted<T, C, FT, IT, PFT, PIT>(
+    start: &T,
+    goal: &T,
+    successors: FT,
+    predecessors: PFT,
+) -> Option<C>
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    PFT: Fn(&T) -> PIT,
+    PIT: IntoIterator<Item = (T, C)>,
+{
+    bidirectional_shortest_path_with_stats(start, goal, successors, predecessors).0
+}
+
+// Like `bidirectional_shortest_path_weighted`, but also returns the stats of both searches added
+// together.
+pub fn bidirectional_shortest_path_with_stats<T, C, FT, IT, PFT, PIT>(
+    start: &T,
+    goal: &T,
+    successors: FT,
+    predecessors: PFT,
+) -> (Option<C>, Stats)
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+    PFT: Fn(&T) -> PIT,
+    PIT: IntoIterator<Item = (T, C)>,
+{
+    let mut stats = Stats::default();
+    let mut forward = HalfSearch::new(start, &mut stats);
+    let mut backward = HalfSearch::new(goal, &mut stats);
+    // The shortest distance of the paths found so far, i.e. the ones that go through a node that
+    // has been reached from both ends.
+    let mut best_dist = (start == goal).then_some(C::ZERO);
+
+    while let (Some(forward_min), Some(backward_min)) = (
+        forward.min_distance(&mut stats),
+        backward.min_distance(&mut stats),
+    ) {
+        // Any path not found yet must go through unsettled nodes on both sides, so it can't be
+        // shorter than the closest unsettled nodes' distances added together.
+        if best_dist.is_some_and(|best| forward_min + backward_min >= best) {
+            break;
+        }
+        // Expand the side that has the closest node, so both sides advance at a similar pace.
+        if forward_min <= backward_min {
+            forward.expand(&successors, &backward, &mut best_dist, &mut stats);
+        } else {
+            backward.expand(&predecessors, &forward, &mut best_dist, &mut stats);
+        }
+    }
+    (best_dist, stats)
+}
+
+// One of the two searches of a bidirectional search.
+struct HalfSearch<T: Eq, C: Ord> {
+    unvisited: BinaryHeap<Node<T, C>>,
+    distances: HashMap<T, C>,
+}
+
+impl<T, C> HalfSearch<T, C>
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+{
+    fn new(start: &T, stats: &mut Stats) -> Self {
+        let mut distances = HashMap::default();
+        distances.insert(start.clone(), C::ZERO);
+        stats.pushed += 1;
+        HalfSearch {
+            unvisited: BinaryHeap::from([Node {
+                value: start.clone(),
+                distance: C::ZERO,
+                estimate: C::ZERO,
+            }]),
+            distances,
+        }
+    }
+
+    // Returns the distance of the closest unsettled node, discarding stale nodes along the way.
+    fn min_distance(&mut self, stats: &mut Stats) -> Option<C> {
+        while let Some(node) = self.unvisited.peek() {
+            if self.distances[&node.value] < node.distance {
+                self.unvisited.pop();
+                stats.stale_popped += 1;
+            } else {
+                return Some(node.distance);
+            }
+        }
+        None
+    }
+
+    // Settles the closest node, updating the best distance with the paths that go through the
+    // nodes already reached by the other search. Must be called after `min_distance`.
+    fn expand<FT, IT>(
+        &mut self,
+        successors: &FT,
+        other: &HalfSearch<T, C>,
+        best_dist: &mut Option<C>,
+        stats: &mut Stats,
+    ) where
+        FT: Fn(&T) -> IT,
+        IT: IntoIterator<Item = (T, C)>,
+    {
+        let Node {
+            value, distance, ..
+        } = self.unvisited.pop().unwrap();
+        stats.expanded += 1;
+        for (succ, cost) in successors(&value) {
+            debug_assert!(cost >= C::ZERO, "edge costs should not be negative");
+            let path_dist = distance + cost;
+            if let Some(&other_dist) = other.distances.get(&succ) {
+                let total_dist = path_dist + other_dist;
+                if best_dist.is_none_or(|best| total_dist < best) {
+                    *best_dist = Some(total_dist);
+                }
+            }
+            if self
+                .distances
+                .get(&succ)
+                .is_none_or(|&succ_dist| path_dist < succ_dist)
+            {
+                self.distances.insert(succ.clone(), path_dist);
+                self.unvisited.push(Node {
+                    value: succ,
+                    distance: path_dist,
+                    estimate: path_dist,
+                });
+                stats.pushed += 1;
+            }
+        }
+    }
+}
+
+// Iterates the nodes reachable from a start node in the order Dijkstra's algorithm settles them,
+// that is, by increasing distance, together with their distances.
+#[allow(dead_code)]
+pub fn settled_nodes<T, FT, IT>(start: &T, successors: FT) -> impl Iterator<Item = (T, usize)>
+where
+    T: Eq + Hash + Clone,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+{
+    settled_nodes_weighted(start, move |node: &T| {
+        successors(node).into_iter().map(|succ| (succ, 1))
+    })
+}
+
+#[allow(dead_code)]
+pub fn settled_nodes_weighted<T, C, FT, IT>(start: &T, successors: FT) -> SettledNodes<T, C, FT>
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+{
+    let mut distances = HashMap::default();
+    distances.insert(start.clone(), C::ZERO);
+    SettledNodes {
+        unvisited: BinaryHeap::from([Node {
+            value: start.clone(),
+            distance: C::ZERO,
+            estimate: C::ZERO,
+        }]),
+        distances,
+        successors,
+    }
+}
+
+#[allow(dead_code)]
+pub struct SettledNodes<T: Eq, C: Ord, FT> {
+    unvisited: BinaryHeap<Node<T, C>>,
+    distances: HashMap<T, C>,
+    successors: FT,
+}
+
+impl<T, C, FT, IT> Iterator for SettledNodes<T, C, FT>
+where
+    T: Eq + Hash + Clone,
+    C: Cost,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = (T, C)>,
+{
+    type Item = (T, C);
+
+    fn next(&mut self) -> Option<(T, C)> {
+        loop {
+            let Node {
+                value, distance, ..
+            } = self.unvisited.pop()?;
+            if self.distances[&value] < distance {
+                continue;
+            }
+            for (succ, cost) in (self.successors)(&value) {
+                debug_assert!(cost >= C::ZERO, "edge costs should not be negative");
+                let path_dist = distance + cost;
+                if self
+                    .distances
+                    .get(&succ)
+                    .is_none_or(|&succ_dist| path_dist < succ_dist)
+                {
+                    self.distances.insert(succ.clone(), path_dist);
+                    self.unvisited.push(Node {
+                        value: succ,
+                        distance: path_dist,
+                        estimate: path_dist,
+                    });
+                }
+            }
+            return Some((value, distance));
+        }
+    }
+}
+
+// The types that can be used as path costs. Signed types are allowed, e.g. to use the same type as
+// the rest of a puzzle, but the searches only work with non-negative edge costs.
+pub trait Cost: Copy + Ord + Add<Output = Self> + Zero {}
+
+impl<C: Copy + Ord + Add<Output = C> + Zero> Cost for C {}
+
+pub trait Zero {
+    const ZERO: Self;
+}
+
+macro_rules! impl_zero {
+    ($($int:ty),*) => {$(
+        impl Zero for $int {
+            const ZERO: Self = 0;
+        }
+    )*};
+}
+
+impl_zero!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+// Calculates the distances from a start node to all the nodes reachable from it.
+pub fn distances_from<T, FT, IT>(start: &T, successors: FT) -> HashMap<T, usize>
+where
+    T: Eq + Hash + Clone,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+{
+    distances_from_many([start.clone()], successors)
+}
+
+// Like `distances_from`, but starting from many nodes at once, all of them at distance 0. The
+// distance to each node is the distance from the closest start node.
+pub fn distances_from_many<T, FT, IT>(
+    starts: impl IntoIterator<Item = T>,
+    successors: FT,
+) -> HashMap<T, usize>
+where
+    T: Eq + Hash + Clone,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+{
+    // All edges have the same cost, so a simple BFS visits nodes in order of distance, no need for
+    // a priority queue.
+    let mut distances = HashMap::default();
+    let mut unvisited = VecDeque::new();
+    for start in starts {
+        if !distances.contains_key(&start) {
+            distances.insert(start.clone(), 0);
+            unvisited.push_back((start, 0));
+        }
+    }
+
+    while let Some((value, distance)) = unvisited.pop_front() {
+        for succ in successors(&value) {
+            if !distances.contains_key(&succ) {
+                distances.insert(succ.clone(), distance + 1);
+                unvisited.push_back((succ, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+// Like `shortest_path`, but for graphs whose nodes can be mapped to an index in 0..node_count,
+// which allows using a Vec instead of a HashMap to keep track of the visited nodes. Nodes with the
+// same index are considered to be equivalent, so only the first one reached is explored.
+pub fn shortest_path_indexed<T, PT, FT, IT, XT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+    index: XT,
+    node_count: usize,
+) -> Option<usize>
+where
+    T: Clone,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+    XT: Fn(&T) -> usize,
+{
+    // As all edges have a cost of 1, a BFS finds the shortest path.
+    let mut visited = vec![false; node_count];
+    let mut unvisited = VecDeque::from([(start.clone(), 0)]);
+    visited[index(start)] = true;
+
+    while let Some((value, distance)) = unvisited.pop_front() {
+        if is_goal(&value) {
+            return Some(distance);
+        }
+        for succ in successors(&value) {
+            let succ_index = index(&succ);
+            if !visited[succ_index] {
+                visited[succ_index] = true;
+                unvisited.push_back((succ, distance + 1));
+            }
+        }
+    }
+    None
+}
+
+// Like `distances_from`, but for nodes that can be mapped to an index in 0..node_count, like in
+// `shortest_path_indexed`. The distance of each node is stored at its index.
+pub fn distances_from_indexed<T, FT, IT, XT>(
+    start: &T,
+    successors: FT,
+    index: XT,
+    node_count: usize,
+) -> Vec<Option<usize>>
+where
+    T: Clone,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+    XT: Fn(&T) -> usize,
+{
+    let mut distances = vec![None; node_count];
+    let mut unvisited = VecDeque::from([(start.clone(), 0)]);
+    distances[index(start)] = Some(0);
+
+    while let Some((value, distance)) = unvisited.pop_front() {
+        for succ in successors(&value) {
+            let succ_index = index(&succ);
+            if distances[succ_index].is_none() {
+                distances[succ_index] = Some(distance + 1);
+                unvisited.push_back((succ, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+// Like `shortest_path`, but also returns the route from start to goal, both included.
+pub fn shortest_path_with_route<T, PT, FT, IT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+) -> Option<(usize, Vec<T>)>
+where
+    T: Eq + Hash + Clone,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+{
+    let (goal, distance, predecessors) = search_with_predecessors(start, is_goal, successors)?;
+    let mut route = vec![goal];
+    while let Some(preds) = predecessors.get(route.last().unwrap()) {
+        route.push(preds[0].clone());
+    }
+    route.reverse();
+    Some((distance, route))
+}
+
+// Like `shortest_path_with_route`, but returns all the routes of the shortest distance that lead
+// to the first goal node found. Beware that the number of tied routes can be huge on some graphs,
+// like open grids.
+#[allow(dead_code)]
+pub fn all_shortest_paths<T, PT, FT, IT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+) -> Option<(usize, Vec<Vec<T>>)>
+where
+    T: Eq + Hash + Clone,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+{
+    let (goal, distance, predecessors) = search_with_predecessors(start, is_goal, successors)?;
+    let mut routes = vec![];
+    // Walk the predecessors back from the goal, building the routes in reverse.
+    let mut unfinished_routes = vec![vec![goal]];
+    while let Some(route) = unfinished_routes.pop() {
+        match predecessors.get(route.last().unwrap()) {
+            Some(preds) => {
+                for pred in preds {
+                    let mut longer_route = route.clone();
+                    longer_route.push(pred.clone());
+                    unfinished_routes.push(longer_route);
+                }
+            }
+            None => routes.push(route.into_iter().rev().collect()),
+        }
+    }
+    Some((distance, routes))
+}
+
+// Same algorithm as `shortest_path`, but keeping track of all the predecessors of each node that
+// are on some shortest path to it. Returns the goal node found, its distance, and the predecessors.
+fn search_with_predecessors<T, PT, FT, IT>(
+    start: &T,
+    is_goal: PT,
+    successors: FT,
+) -> Option<(T, usize, HashMap<T, Vec<T>>)>
+where
+    T: Eq + Hash + Clone,
+    PT: Fn(&T) -> bool,
+    FT: Fn(&T) -> IT,
+    IT: IntoIterator<Item = T>,
+{
+    let mut unvisited = BinaryHeap::new();
+    let mut distances = HashMap::default();
+    let mut predecessors: HashMap<T, Vec<T>> = HashMap::default();
+
     distances.insert(start.clone(), 0);
     unvisited.push(Node {
         value: start.clone(),
         distance: 0,
+        estimate: 0,
     });
 
     while let Some(min_dist_node) = unvisited.pop() {
-        let Node { value, distance } = min_dist_node;
+        let Node {
+            value, distance, ..
+        } = min_dist_node;
 
         if is_goal(&value) {
-            return Some(distance);
+            return Some((value, distance, predecessors));
         }
 
         if distances[&value] < distance {
@@ -36,12 +824,17 @@ where
 
         for succ in successors(&value) {
             let path_dist = distance + 1;
-            if path_dist < *distances.get(&succ).unwrap_or(&usize::MAX) {
+            let succ_dist = *distances.get(&succ).unwrap_or(&usize::MAX);
+            if path_dist < succ_dist {
                 distances.insert(succ.clone(), path_dist);
+                predecessors.insert(succ.clone(), vec![value.clone()]);
                 unvisited.push(Node {
                     value: succ,
                     distance: path_dist,
+                    estimate: path_dist,
                 });
+            } else if path_dist == succ_dist {
+                predecessors.get_mut(&succ).unwrap().push(value.clone());
             }
         }
     }
@@ -49,21 +842,530 @@ where
 }
 
 #[derive(PartialEq, Eq)]
-struct Node<T: Eq> {
+struct Node<T: Eq, C: Ord = usize> {
     value: T,
-    distance: usize,
+    distance: C,
+    // The distance plus the heuristic estimate of the remaining distance to the goal. Equal to the
+    // distance when no heuristic is used.
+    estimate: C,
 }
 
-impl<T: Eq> Ord for Node<T> {
+impl<T: Eq, C: Ord> Ord for Node<T, C> {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Compare distances in the other way so the binary heap is min-sorted.
-        other.distance.cmp(&self.distance)
+        // Compare estimates in the other way so the binary heap is min-sorted. On equal estimates,
+        // prefer the node that is further from the start, as it's probably closer to the goal.
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then(self.distance.cmp(&other.distance))
     }
 }
 
 // Rust requires this for some reason too.
-impl<T: Eq> PartialOrd for Node<T> {
+impl<T: Eq, C: Ord> PartialOrd for Node<T, C> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // A random directed graph as a list of (from, to, cost) edges. Costs may be zero.
+    fn random_graph(rng: &mut Rng) -> (usize, Vec<(usize, usize, u32)>) {
+        let node_count = 1 + rng.below(25);
+        let edge_count = rng.below(node_count * 3);
+        let edges = (0..edge_count)
+            .map(|_| {
+                (
+                    rng.below(node_count),
+                    rng.below(node_count),
+                    rng.below(10) as u32,
+                )
+            })
+            .collect();
+        (node_count, edges)
+    }
+
+    #[test]
+    fn bidirectional_matches_shortest_path() {
+        let mut rng = Rng::new(0x2022_1212);
+        for _ in 0..300 {
+            let (node_count, edges) = random_graph(&mut rng);
+            let successors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+                edges.map(|&(_, to, cost)| (to, cost))
+            };
+            let predecessors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(_, to, _)| to == node);
+                edges.map(|&(from, _, cost)| (from, cost))
+            };
+            let unit_successors = |node: &usize| successors(node).map(|(succ, _)| succ);
+            let unit_predecessors = |node: &usize| predecessors(node).map(|(pred, _)| pred);
+
+            for start in 0..node_count {
+                for goal in 0..node_count {
+                    assert_eq!(
+                        bidirectional_shortest_path_weighted(
+                            &start,
+                            &goal,
+                            successors,
+                            predecessors
+                        ),
+                        shortest_path_weighted(&start, |&n| n == goal, successors),
+                        "weighted path from {start} to {goal} on {edges:?}"
+                    );
+                    assert_eq!(
+                        bidirectional_shortest_path(
+                            &start,
+                            &goal,
+                            unit_successors,
+                            unit_predecessors
+                        ),
+                        shortest_path(&start, |&n| n == goal, unit_successors),
+                        "unit path from {start} to {goal} on {edges:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    // All the paths from start to goal with exactly `len` edges, found by brute force.
+    fn paths_of_len(
+        edges: &[(usize, usize, u32)],
+        start: usize,
+        goal: usize,
+        len: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut paths = vec![];
+        let mut unfinished = vec![vec![start]];
+        while let Some(path) = unfinished.pop() {
+            let last = *path.last().unwrap();
+            if path.len() == len + 1 {
+                if last == goal {
+                    paths.push(path);
+                }
+                continue;
+            }
+            for &(_, to, _) in edges.iter().filter(|&&(from, _, _)| from == last) {
+                let mut longer_path = path.clone();
+                longer_path.push(to);
+                unfinished.push(longer_path);
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn routes_are_shortest_paths() {
+        let mut rng = Rng::new(0x2022_1229);
+        for _ in 0..300 {
+            let (node_count, edges) = random_graph(&mut rng);
+            let successors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+                edges.map(|&(_, to, _)| to)
+            };
+            let is_edge =
+                |from: usize, to: usize| edges.iter().any(|&(f, t, _)| (f, t) == (from, to));
+            for start in 0..node_count {
+                for goal in 0..node_count {
+                    let is_goal = |&n: &usize| n == goal;
+                    let distance = shortest_path(&start, is_goal, successors);
+                    let route = shortest_path_with_route(&start, is_goal, successors);
+                    let all_routes = all_shortest_paths(&start, is_goal, successors);
+                    let Some(distance) = distance else {
+                        assert!(route.is_none() && all_routes.is_none());
+                        continue;
+                    };
+
+                    let (route_distance, route) = route.unwrap();
+                    assert_eq!(route_distance, distance);
+                    assert_eq!(route.len(), distance + 1);
+                    assert_eq!((route[0], route[distance]), (start, goal));
+                    assert!(route.windows(2).all(|step| is_edge(step[0], step[1])));
+
+                    // Parallel edges make for different paths with the same nodes, so both sides
+                    // can have repeated routes, but they should have the same number of them.
+                    let (all_routes_distance, mut all_routes) = all_routes.unwrap();
+                    assert_eq!(all_routes_distance, distance);
+                    all_routes.sort();
+                    let mut expected_routes = paths_of_len(&edges, start, goal, distance);
+                    expected_routes.sort();
+                    assert_eq!(
+                        all_routes, expected_routes,
+                        "routes from {start} to {goal} on {edges:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn all_shortest_paths_on_a_grid() {
+        // On a 3x3 grid, there are 6 ways to go from one corner to the opposite one.
+        let successors = |&(x, y): &(u8, u8)| {
+            [(x + 1, y), (x, y + 1)]
+                .into_iter()
+                .filter(|&(x, y)| x < 3 && y < 3)
+        };
+        let (distance, routes) = all_shortest_paths(&(0, 0), |&p| p == (2, 2), successors).unwrap();
+        assert_eq!(distance, 4);
+        assert_eq!(routes.len(), 6);
+        assert!(routes
+            .iter()
+            .all(|route| route.len() == 5 && route[0] == (0, 0)));
+        let (_, route) = shortest_path_with_route(&(0, 0), |&p| p == (2, 2), successors).unwrap();
+        assert!(routes.contains(&route));
+    }
+
+    #[test]
+    fn weighted_matches_brute_force() {
+        let mut rng = Rng::new(0x2022_1230);
+        for _ in 0..300 {
+            let (node_count, edges) = random_graph(&mut rng);
+            // Relax every edge as many times as there are nodes, like Bellman-Ford.
+            let mut expected = vec![vec![None; node_count]; node_count];
+            for (start, distances) in expected.iter_mut().enumerate() {
+                distances[start] = Some(0);
+                for _ in 0..node_count {
+                    for &(from, to, cost) in &edges {
+                        if let Some(from_dist) = distances[from] {
+                            let path_dist = from_dist + cost as i64;
+                            if distances[to].is_none_or(|to_dist| path_dist < to_dist) {
+                                distances[to] = Some(path_dist);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let successors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+                edges.map(|&(_, to, cost)| (to, cost as i64))
+            };
+            for (start, distances) in expected.iter().enumerate() {
+                for (goal, &distance) in distances.iter().enumerate() {
+                    assert_eq!(
+                        shortest_path_weighted(&start, |&n| n == goal, successors),
+                        distance,
+                        "path from {start} to {goal} on {edges:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "edge costs should not be negative")]
+    fn negative_costs_are_rejected() {
+        let successors = |&node: &i32| [(node + 1, -1)];
+        shortest_path_weighted(&0, |&n| n == 3, successors);
+    }
+
+    #[test]
+    fn astar_matches_dijkstra_on_grids() {
+        let mut rng = Rng::new(0x2022_1224);
+        let (mut astar_expanded, mut dijkstra_expanded) = (0, 0);
+        for _ in 0..100 {
+            // A 10x10 grid with about a quarter of the tiles walled off.
+            let walls: Vec<bool> = (0..100).map(|_| rng.below(4) == 0).collect();
+            let goal = (rng.below(10) as i32, rng.below(10) as i32);
+            let successors = |&(x, y): &(i32, i32)| {
+                [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+                    .into_iter()
+                    .filter(|&(x, y)| {
+                        (0..10).contains(&x)
+                            && (0..10).contains(&y)
+                            && !walls[(y * 10 + x) as usize]
+                    })
+                    .map(|succ| (succ, 1))
+            };
+            let manhattan = |&(x, y): &(i32, i32)| x.abs_diff(goal.0) + y.abs_diff(goal.1);
+
+            let is_goal = |&node: &(i32, i32)| node == goal;
+            let (astar_dist, astar_stats) =
+                astar_weighted_with_stats(&(0, 0), is_goal, successors, manhattan);
+            let (dijkstra_dist, dijkstra_stats) =
+                astar_weighted_with_stats(&(0, 0), is_goal, successors, |_| 0);
+            assert_eq!(astar_dist, dijkstra_dist);
+            astar_expanded += astar_stats.expanded;
+            dijkstra_expanded += dijkstra_stats.expanded;
+        }
+        assert!(astar_expanded < dijkstra_expanded);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "heuristic should be consistent")]
+    fn inconsistent_heuristics_are_rejected() {
+        // Overestimates by one everywhere but at the goal, so it drops by 2 on the last step.
+        let heuristic = |&node: &usize| if node == 3 { 0 } else { 4 - node };
+        astar(&0, |&n| n == 3, |&node| [node + 1], heuristic);
+    }
+
+    // An endless line of nodes, so only the limits can stop a search for an unreachable goal.
+    fn endless_line(&node: &u64) -> [(u64, u64); 1] {
+        [(node + 1, 1)]
+    }
+
+    #[test]
+    fn unreachable_goals() {
+        let successors = |&node: &u64| [(node + 1, 1)].into_iter().filter(|&(n, _)| n < 10);
+        let (outcome, stats) =
+            astar_weighted_with_limits(&0, |&n| n == 20, successors, |_| 0, &Limits::default());
+        assert_eq!(outcome, Outcome::Unreachable);
+        assert_eq!(stats.expanded, 10);
+    }
+
+    #[test]
+    fn max_expanded_limit() {
+        let limits = Limits {
+            max_expanded: Some(100),
+            ..Limits::default()
+        };
+        let (outcome, stats) =
+            astar_weighted_with_limits(&0, |_| false, endless_line, |_| 0, &limits);
+        assert_eq!(outcome, Outcome::LimitReached);
+        assert_eq!(stats.expanded, 100);
+
+        // The goal is found before reaching the limit.
+        let (outcome, _) =
+            astar_weighted_with_limits(&0, |&n| n == 99, endless_line, |_| 0, &limits);
+        assert_eq!(outcome, Outcome::Found(99));
+    }
+
+    #[test]
+    fn max_distance_limit() {
+        let limits = Limits {
+            max_distance: Some(50),
+            ..Limits::default()
+        };
+        let (outcome, stats) =
+            astar_weighted_with_limits(&0, |_| false, endless_line, |_| 0, &limits);
+        assert_eq!(outcome, Outcome::LimitReached);
+        assert_eq!(stats.expanded, 51);
+
+        // With a heuristic, the search can stop as soon as the goal is known to be too far.
+        let heuristic = |&n: &u64| 1000u64.saturating_sub(n);
+        let (outcome, stats) =
+            astar_weighted_with_limits(&0, |&n| n == 1000, endless_line, heuristic, &limits);
+        assert_eq!(outcome, Outcome::LimitReached);
+        assert_eq!(stats.expanded, 0);
+
+        let (outcome, _) =
+            astar_weighted_with_limits(&0, |&n| n == 50, endless_line, |_| 0, &limits);
+        assert_eq!(outcome, Outcome::Found(50));
+    }
+
+    #[test]
+    fn deadline_and_cancel_limits() {
+        let past_deadline = Limits {
+            deadline: Some(Instant::now()),
+            ..Limits::default()
+        };
+        let (outcome, stats) =
+            astar_weighted_with_limits(&0, |_| false, endless_line, |_| 0, &past_deadline);
+        assert_eq!(outcome, Outcome::LimitReached);
+        assert_eq!(stats.expanded, 0);
+
+        // Cancel the search while it's running. The flag is only checked every
+        // LIMITS_CHECK_INTERVAL expanded nodes, so the search goes on until the next check.
+        struct CancelAt<'a>(u64, &'a AtomicBool);
+        impl Visitor<u64, u64> for CancelAt<'_> {
+            fn on_pop(&mut self, &node: &u64, _distance: u64) {
+                if node == self.0 {
+                    self.1.store(true, Relaxed);
+                }
+            }
+        }
+        let cancel = AtomicBool::new(false);
+        let limits = Limits {
+            cancel: Some(&cancel),
+            ..Limits::default()
+        };
+        let mut visitor = CancelAt(10, &cancel);
+        let (outcome, stats) =
+            astar_weighted_visiting(&0, |_| false, endless_line, |_| 0, &limits, &mut visitor);
+        assert_eq!(outcome, Outcome::LimitReached);
+        assert_eq!(stats.expanded, LIMITS_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn routes_with_limits() {
+        let mut rng = Rng::new(0x2022_1227);
+        for _ in 0..300 {
+            let (node_count, edges) = random_graph(&mut rng);
+            let successors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+                edges.map(|&(_, to, cost)| (to, cost))
+            };
+            let goal = rng.below(node_count);
+            let expected = shortest_path_weighted(&0, |&n| n == goal, successors);
+            let (outcome, _) = astar_weighted_with_route(
+                &0,
+                |&n| n == goal,
+                successors,
+                |_| 0,
+                &Limits::default(),
+            );
+            match outcome {
+                Outcome::Found((distance, route)) => {
+                    assert_eq!(Some(distance), expected);
+                    assert_eq!((route[0], route[route.len() - 1]), (0, goal));
+                    // The route must follow edges, and add up to the distance.
+                    let route_cost = route.windows(2).map(|pair| {
+                        let step_costs = successors(&pair[0]).filter(|&(to, _)| to == pair[1]);
+                        step_costs
+                            .map(|(_, cost)| cost)
+                            .min()
+                            .expect("route should follow edges")
+                    });
+                    assert_eq!(route_cost.sum::<u32>(), distance);
+                }
+                Outcome::Unreachable => assert_eq!(expected, None),
+                Outcome::LimitReached => unreachable!("there are no limits"),
+            }
+        }
+    }
+
+    #[test]
+    fn settled_nodes_by_increasing_distance() {
+        let mut rng = Rng::new(0x2022_1225);
+        for _ in 0..300 {
+            let (node_count, edges) = random_graph(&mut rng);
+            let successors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+                edges.map(|&(_, to, cost)| (to, cost))
+            };
+            let settled: Vec<_> = settled_nodes_weighted(&0, successors).collect();
+            assert!(settled.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+            // Every reachable node is settled exactly once, at its shortest distance.
+            let mut settled_count = 0;
+            for goal in 0..node_count {
+                let distance = shortest_path_weighted(&0, |&n| n == goal, successors);
+                let settled_distances = settled.iter().filter(|&&(node, _)| node == goal);
+                let settled_distances: Vec<_> = settled_distances.map(|&(_, d)| d).collect();
+                assert_eq!(settled_distances, Vec::from_iter(distance));
+                settled_count += settled_distances.len();
+            }
+            assert_eq!(settled_count, settled.len());
+        }
+
+        // On a grid, the nodes at each distance form a diamond around the start.
+        let grid = |&(x, y): &(i32, i32)| [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)];
+        let settled: Vec<_> = settled_nodes(&(0, 0), grid)
+            .take_while(|&(_, distance)| distance <= 10)
+            .collect();
+        assert_eq!(settled.len(), 1 + 2 * 10 * 11);
+        for ((x, y), distance) in settled {
+            assert_eq!(x.unsigned_abs() + y.unsigned_abs(), distance as u32);
+        }
+    }
+
+    // Records every call of a visitor, as (step, node, distance).
+    #[derive(Default)]
+    struct Recorder(Vec<(&'static str, usize, u32)>);
+
+    impl Visitor<usize, u32> for Recorder {
+        fn on_push(&mut self, &node: &usize, distance: u32) {
+            self.0.push(("push", node, distance));
+        }
+        fn on_pop(&mut self, &node: &usize, distance: u32) {
+            self.0.push(("pop", node, distance));
+        }
+        fn on_goal(&mut self, &node: &usize, distance: u32) {
+            self.0.push(("goal", node, distance));
+        }
+        fn on_relax(&mut self, _from: &usize, &to: &usize, distance: u32) {
+            self.0.push(("relax", to, distance));
+        }
+    }
+
+    #[test]
+    fn visitor_callbacks_on_a_small_graph() {
+        // 0 -> 1 costs 5 at first, but going through 2 is shorter, so 1 is relaxed twice.
+        let edges = [(0, 1, 5), (0, 2, 1), (2, 1, 1), (1, 3, 1)];
+        let successors = |&node: &usize| {
+            let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+            edges.map(|&(_, to, cost)| (to, cost))
+        };
+        let mut recorder = Recorder::default();
+        let (outcome, stats) = astar_weighted_visiting(
+            &0,
+            |&n| n == 3,
+            successors,
+            |_| 0,
+            &Limits::default(),
+            &mut recorder,
+        );
+        assert_eq!(outcome, Outcome::Found(3));
+        assert_eq!(
+            recorder.0,
+            [
+                ("push", 0, 0),
+                ("pop", 0, 0),
+                ("relax", 1, 5),
+                ("push", 1, 5),
+                ("relax", 2, 1),
+                ("push", 2, 1),
+                ("pop", 2, 1),
+                ("relax", 1, 2),
+                ("push", 1, 2),
+                ("pop", 1, 2),
+                ("relax", 3, 3),
+                ("push", 3, 3),
+                ("goal", 3, 3),
+            ]
+        );
+        // The stale entry for 1 at distance 5 is never popped, as the goal is found before.
+        assert_eq!(
+            (stats.pushed, stats.expanded, stats.stale_popped),
+            (5, 3, 0)
+        );
+    }
+
+    #[test]
+    fn visitor_callbacks_match_stats() {
+        let mut rng = Rng::new(0x2022_1226);
+        for _ in 0..300 {
+            let (node_count, edges) = random_graph(&mut rng);
+            let successors = |&node: &usize| {
+                let edges = edges.iter().filter(move |&&(from, _, _)| from == node);
+                edges.map(|&(_, to, cost)| (to, cost))
+            };
+            let goal = rng.below(node_count);
+            let mut recorder = Recorder::default();
+            let (outcome, stats) = astar_weighted_visiting(
+                &0,
+                |&n| n == goal,
+                successors,
+                |_| 0,
+                &Limits::default(),
+                &mut recorder,
+            );
+            let steps = |name| recorder.0.iter().filter(move |&&(step, _, _)| step == name);
+            assert_eq!(steps("push").count(), stats.pushed);
+            assert_eq!(steps("pop").count(), stats.expanded);
+            // Every relaxed node is pushed right away, and the start is pushed without relaxing.
+            assert_eq!(steps("relax").count() + 1, stats.pushed);
+            // Popped nodes are settled, so they come by increasing distance and only once.
+            let pops: Vec<_> = steps("pop")
+                .map(|&(_, node, distance)| (node, distance))
+                .collect();
+            assert!(pops.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+            assert!(pops
+                .iter()
+                .all(|&(node, _)| pops.iter().filter(|p| p.0 == node).count() == 1));
+            let goals: Vec<_> = steps("goal")
+                .map(|&(_, node, distance)| (node, distance))
+                .collect();
+            match outcome {
+                Outcome::Found(distance) => assert_eq!(goals, [(goal, distance)]),
+                _ => assert_eq!(goals, []),
+            }
+        }
+    }
+}
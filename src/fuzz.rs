@@ -0,0 +1,292 @@
+// A small fuzzer for the input parsers, which doesn't need cargo-fuzz nor a nightly toolchain. It
+// feeds each parser random bytes and mutations of valid inputs, looking for inputs that make it
+// panic instead of returning an error. Inputs that found bugs are kept as regression files on the
+// fuzz/<target name> directories, and those, along with a fixed batch of fuzzed inputs, are checked
+// on every `cargo test`.
+//
+// Only the parsers below are covered. The ones of the other days still assume well-formed puzzle
+// inputs, and panic on anything else.
+use crate::rng::Rng;
+use crate::{
+    day_04_camp_cleanup, day_05_supply_stacks, day_07_no_space_left_on_device,
+    day_07_no_space_left_on_device_with_tree, day_09_rope_bridge, day_10_cathode_ray_tube,
+    day_11_monkey_in_the_middle, day_13_distress_signal, day_22_monkey_map, day_25_full_of_hot_air,
+    GENERATORS,
+};
+use std::{
+    collections::HashSet,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+pub struct Target {
+    pub day: usize,
+    pub name: &'static str,
+    // Parses an input, ignoring any errors. Any panic is a bug.
+    parse: fn(&str),
+}
+
+pub const TARGETS: [Target; 10] = [
+    Target {
+        day: 4,
+        name: "day_04_parse_assignments",
+        parse: day_04_camp_cleanup::fuzz_target,
+    },
+    Target {
+        day: 5,
+        name: "day_05_parse_stacks",
+        parse: day_05_supply_stacks::fuzz_target,
+    },
+    Target {
+        day: 7,
+        name: "day_07_parse_terminal_output",
+        parse: day_07_no_space_left_on_device::fuzz_target,
+    },
+    Target {
+        day: 7,
+        name: "day_07_parse_fs_tree",
+        parse: day_07_no_space_left_on_device_with_tree::fuzz_target,
+    },
+    Target {
+        day: 9,
+        name: "day_09_parse_motions",
+        parse: day_09_rope_bridge::fuzz_target,
+    },
+    Target {
+        day: 10,
+        name: "day_10_parse_instructions",
+        parse: day_10_cathode_ray_tube::fuzz_target,
+    },
+    Target {
+        day: 11,
+        name: "day_11_parse_monkey",
+        parse: day_11_monkey_in_the_middle::fuzz_target,
+    },
+    Target {
+        day: 13,
+        name: "day_13_parse_packet",
+        parse: day_13_distress_signal::fuzz_target,
+    },
+    Target {
+        day: 22,
+        name: "day_22_parse_instructions",
+        parse: day_22_monkey_map::fuzz_target,
+    },
+    Target {
+        day: 25,
+        name: "day_25_parse_snafu",
+        parse: day_25_full_of_hot_air::fuzz_target,
+    },
+];
+
+impl Target {
+    fn regressions_dir(&self) -> PathBuf {
+        PathBuf::from("fuzz").join(self.name)
+    }
+
+    fn regressions(&self) -> Vec<(PathBuf, Vec<u8>)> {
+        let Ok(entries) = fs::read_dir(self.regressions_dir()) else {
+            return vec![];
+        };
+        let mut regressions: Vec<_> = entries
+            .map(|entry| entry.expect("error reading regressions dir").path())
+            .map(|path| {
+                let input = fs::read(&path).expect("error reading regression file");
+                (path, input)
+            })
+            .collect();
+        regressions.sort();
+        regressions
+    }
+
+    // Valid inputs to start mutating from: the day's sample and a few generated ones.
+    fn corpus(&self) -> Vec<Vec<u8>> {
+        let mut corpus = vec![];
+        if let Ok(sample) = fs::read_to_string(format!("samples/{:02}.txt", self.day)) {
+            let (input, _expected_output) = sample.split_once("\n~~~\n").unwrap_or((&sample, ""));
+            corpus.push(input.as_bytes().to_vec());
+        }
+        let generator = GENERATORS[self.day - 1];
+        for seed in 0..5 {
+            corpus.push(generator(&mut Rng::new(seed), 8).into_bytes());
+        }
+        corpus
+    }
+
+    fn panics_on(&self, input: &[u8]) -> bool {
+        let input = String::from_utf8_lossy(input);
+        panic::catch_unwind(AssertUnwindSafe(|| (self.parse)(&input))).is_err()
+    }
+}
+
+struct Fuzzer {
+    rng: Rng,
+    corpus: Vec<Vec<u8>>,
+    // The bytes that appear on valid inputs, which are more likely to reach deep into a parser than
+    // completely random ones.
+    interesting_bytes: Vec<u8>,
+}
+
+impl Fuzzer {
+    fn new(target: &Target, seed: u64) -> Fuzzer {
+        let corpus = target.corpus();
+        let mut interesting_bytes: Vec<u8> = corpus.iter().flatten().copied().collect();
+        interesting_bytes.sort();
+        interesting_bytes.dedup();
+        Fuzzer {
+            rng: Rng::new(seed),
+            corpus,
+            interesting_bytes,
+        }
+    }
+
+    fn next_input(&mut self) -> Vec<u8> {
+        if self.rng.chance(0.2) {
+            let len = self.rng.below(65);
+            return (0..len).map(|_| self.random_byte()).collect();
+        }
+        let mut input = self.rng.choose(&self.corpus).clone();
+        for _ in 0..self.rng.range(1..=8) {
+            self.mutate(&mut input);
+        }
+        input
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        if self.rng.chance(0.8) {
+            *self.rng.choose(&self.interesting_bytes)
+        } else {
+            self.rng.below(256) as u8
+        }
+    }
+
+    fn mutate(&mut self, input: &mut Vec<u8>) {
+        let len = input.len();
+        match self.rng.below(5) {
+            0 if len > 0 => {
+                let i = self.rng.below(len);
+                input[i] = self.random_byte();
+            }
+            1 => {
+                let i = self.rng.below(len + 1);
+                let byte = self.random_byte();
+                input.insert(i, byte);
+            }
+            2 if len > 0 => {
+                let start = self.rng.below(len);
+                let end = start + 1 + self.rng.below((len - start).min(16));
+                input.drain(start..end);
+            }
+            3 if len > 0 => {
+                let start = self.rng.below(len);
+                let end = start + 1 + self.rng.below((len - start).min(16));
+                let copied = input[start..end].to_vec();
+                let i = self.rng.below(len + 1);
+                input.splice(i..i, copied);
+            }
+            _ => input.truncate(self.rng.below(len + 1)),
+        }
+    }
+}
+
+// Fuzzes a target for a number of iterations, and saves a regression file for each different place
+// where the parser panicked. Returns the number of places found, including the ones of existing
+// regression files that still panic.
+pub fn fuzz(target: &Target, seed: u64, iterations: usize) -> usize {
+    // Silence the panic messages, but keep track of where each panic happened to tell findings
+    // apart.
+    let last_panic_location = Arc::new(Mutex::new(String::new()));
+    let hook_panic_location = last_panic_location.clone();
+    panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map_or("unknown".to_string(), |l| l.to_string());
+        *hook_panic_location.lock().unwrap() = location;
+    }));
+
+    // Known findings are not saved again.
+    let mut panic_locations = HashSet::new();
+    for (path, input) in target.regressions() {
+        if target.panics_on(&input) {
+            let location = last_panic_location.lock().unwrap().clone();
+            println!("Panic at {location} still happens on {}", path.display());
+            panic_locations.insert(location);
+        }
+    }
+
+    let mut fuzzer = Fuzzer::new(target, seed);
+    for iteration in 0..iterations {
+        let input = fuzzer.next_input();
+        if !target.panics_on(&input) {
+            continue;
+        }
+        let location = last_panic_location.lock().unwrap().clone();
+        if panic_locations.insert(location.clone()) {
+            let panics_at_location = |input: &[u8]| {
+                target.panics_on(input) && *last_panic_location.lock().unwrap() == location
+            };
+            let input = minimize(input, panics_at_location);
+            let dir = target.regressions_dir();
+            let path = dir.join(format!("seed_{seed}_iteration_{iteration}.txt"));
+            fs::create_dir_all(&dir).expect("error creating regressions dir");
+            fs::write(&path, &input).expect("error writing regression file");
+            println!("Panic at {location}, saved input to {}", path.display());
+        }
+    }
+
+    let _ = panic::take_hook();
+    panic_locations.len()
+}
+
+// Removes parts of an input for as long as it keeps failing, so that findings are easier to read.
+fn minimize(mut input: Vec<u8>, fails: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut chunk_len = input.len() / 2;
+    while chunk_len > 0 {
+        let mut start = 0;
+        while start < input.len() {
+            let end = (start + chunk_len).min(input.len());
+            let mut smaller = input.clone();
+            smaller.drain(start..end);
+            if fails(&smaller) {
+                input = smaller;
+            } else {
+                start += chunk_len;
+            }
+        }
+        chunk_len /= 2;
+    }
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsers_dont_panic() {
+        // The panics are reported below once the default hook is back, which would otherwise flood
+        // the output with a message for each one.
+        panic::set_hook(Box::new(|_| {}));
+        let mut failures = vec![];
+        for target in TARGETS.iter() {
+            let name = target.name;
+            for (path, input) in target.regressions() {
+                if target.panics_on(&input) {
+                    failures.push(format!("{name} panics on {}", path.display()));
+                }
+            }
+            let mut fuzzer = Fuzzer::new(target, 0);
+            for _ in 0..2000 {
+                let input = fuzzer.next_input();
+                if target.panics_on(&input) {
+                    let input_text = String::from_utf8_lossy(&input);
+                    failures.push(format!("{name} panics on {input_text:?}"));
+                }
+            }
+        }
+        let _ = panic::take_hook();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
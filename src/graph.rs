@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+// Distances between every pair of nodes of a graph. `distances[a][b]` is None if b is not
+// reachable from a.
+pub type DistanceMatrix = Vec<Vec<Option<usize>>>;
+
+// Computes the distances between all pairs of nodes of an unweighted graph given as an adjacency
+// list, where `adjacency[a]` contains the nodes that can be reached from node a in one step. The
+// puzzles' graphs are sparse, so they use `all_pairs_bfs`, but this is better for dense graphs.
+#[allow(dead_code)]
+pub fn floyd_warshall(adjacency: &[Vec<usize>]) -> DistanceMatrix {
+    let node_count = adjacency.len();
+    let mut distances = vec![vec![None; node_count]; node_count];
+    for (node, neighbors) in adjacency.iter().enumerate() {
+        distances[node][node] = Some(0);
+        for &neighbor in neighbors {
+            if neighbor != node {
+                distances[node][neighbor] = Some(1);
+            }
+        }
+    }
+
+    for k in 0..node_count {
+        let distances_from_k = distances[k].clone();
+        for distances_from_i in distances.iter_mut() {
+            let Some(dist_ik) = distances_from_i[k] else {
+                continue;
+            };
+            for (j, dist_kj) in distances_from_k.iter().enumerate() {
+                let Some(dist_kj) = dist_kj else {
+                    continue;
+                };
+                let path_dist = dist_ik + dist_kj;
+                if distances_from_i[j].is_none_or(|dist_ij| path_dist < dist_ij) {
+                    distances_from_i[j] = Some(path_dist);
+                }
+            }
+        }
+    }
+    distances
+}
+
+// Same as `floyd_warshall`, but running a BFS from each node. Faster for sparse graphs.
+pub fn all_pairs_bfs(adjacency: &[Vec<usize>]) -> DistanceMatrix {
+    (0..adjacency.len())
+        .map(|start| bfs_distances(adjacency, start))
+        .collect()
+}
+
+// Distances from a start node to every node of an unweighted graph.
+pub fn bfs_distances(adjacency: &[Vec<usize>], start: usize) -> Vec<Option<usize>> {
+    let mut distances = vec![None; adjacency.len()];
+    distances[start] = Some(0);
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((node, dist)) = queue.pop_front() {
+        for &neighbor in adjacency[node].iter() {
+            if distances[neighbor].is_none() {
+                distances[neighbor] = Some(dist + 1);
+                queue.push_back((neighbor, dist + 1));
+            }
+        }
+    }
+    distances
+}
+
+// A graph reduced to a subset of "interesting" nodes of another graph, where the distances between
+// them are the shortest path distances on the original graph.
+pub struct CompressedGraph {
+    // The original node for each node of the compressed graph.
+    pub nodes: Vec<usize>,
+    pub distances: DistanceMatrix,
+}
+
+impl CompressedGraph {
+    pub fn new(distances: &DistanceMatrix, nodes: &[usize]) -> CompressedGraph {
+        let compressed_distances = nodes
+            .iter()
+            .map(|&a| nodes.iter().map(|&b| distances[a][b]).collect())
+            .collect();
+        CompressedGraph {
+            nodes: nodes.to_vec(),
+            distances: compressed_distances,
+        }
+    }
+
+    pub fn index_of(&self, original_node: usize) -> Option<usize> {
+        self.nodes.iter().position(|&n| n == original_node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property::{check, ints, vecs};
+
+    // Builds a directed graph of up to 8 nodes from a list of numbers, each of which encodes an
+    // edge as `from * 8 + to`. Nodes are numbered up to the highest one mentioned.
+    fn graph_from_edges(edges: &[i64]) -> Vec<Vec<usize>> {
+        let node_count = edges
+            .iter()
+            .map(|&e| (e / 8).max(e % 8) as usize + 1)
+            .max()
+            .unwrap_or(1);
+        let mut adjacency = vec![vec![]; node_count];
+        for &edge in edges {
+            adjacency[(edge / 8) as usize].push((edge % 8) as usize);
+        }
+        adjacency
+    }
+
+    #[test]
+    fn floyd_warshall_agrees_with_bfs() {
+        check(&vecs(ints(0..=63), 0..=20), |edges| {
+            let adjacency = graph_from_edges(edges);
+            floyd_warshall(&adjacency) == all_pairs_bfs(&adjacency)
+        });
+    }
+
+    #[test]
+    fn unreachable_nodes() {
+        // 0 -> 1 -> 2, and 3 on its own.
+        let adjacency = vec![vec![1], vec![2], vec![], vec![3]];
+        let distances = floyd_warshall(&adjacency);
+        assert_eq!(distances[0], [Some(0), Some(1), Some(2), None]);
+        assert_eq!(distances[2], [None, None, Some(0), None]);
+        assert_eq!(distances[3], [None, None, None, Some(0)]);
+
+        let compressed = CompressedGraph::new(&distances, &[2, 0]);
+        assert_eq!(compressed.distances, [[Some(0), None], [Some(2), Some(0)]]);
+        assert_eq!(compressed.index_of(0), Some(1));
+        assert_eq!(compressed.index_of(3), None);
+    }
+}
@@ -1,6 +1,14 @@
 use rayon::prelude::*;
-use std::{env, fs, process::ExitCode, time};
+use rng::Rng;
+use std::{
+    env, fs,
+    io::{BufRead, BufReader},
+    process::ExitCode,
+    time,
+};
 
+mod bitset;
+mod branch_and_bound;
 mod day_01_calorie_counting;
 mod day_02_rock_paper_scissors;
 mod day_03_rucksack_reorganization;
@@ -28,60 +36,187 @@ mod day_23_unstable_diffusion;
 mod day_24_blizzard_basin;
 mod day_25_full_of_hot_air;
 mod dijkstra;
+mod fuzz;
+mod graph;
+#[cfg(test)]
+mod property;
+mod rng;
 mod sample_tests;
+mod streaming;
+
+type DayFn = fn(&str) -> String;
+type StreamingDayFn = fn(&mut dyn BufRead) -> String;
+type GenFn = fn(&mut Rng, usize) -> String;
+type QueryFn = fn(&str, &str) -> Result<String, String>;
+type StreamingQueryFn = fn(&mut dyn BufRead, &str) -> Result<String, String>;
+
+const DAYS: [DayFn; 25] = [
+    day_01_calorie_counting::run,
+    day_02_rock_paper_scissors::run,
+    day_03_rucksack_reorganization::run,
+    day_04_camp_cleanup::run,
+    day_05_supply_stacks::run,
+    day_06_tuning_trouble::run,
+    day_07_no_space_left_on_device::run,
+    day_08_treetop_tree_house::run,
+    day_09_rope_bridge::run,
+    day_10_cathode_ray_tube::run,
+    day_11_monkey_in_the_middle::run,
+    day_12_hill_climbing_algorithm::run,
+    day_13_distress_signal::run,
+    day_14_regolith_reservoir::run,
+    day_15_beacon_exclusion_zone::run,
+    day_16_proboscidea_volcanium::run,
+    day_17_pyroclastic_flow::run,
+    day_18_boiling_boulder::run,
+    day_19_not_enough_minerals::run,
+    day_20_grove_positioning_system::run,
+    day_21_monkey_math::run,
+    day_22_monkey_map::run,
+    day_23_unstable_diffusion::run,
+    day_24_blizzard_basin::run,
+    day_25_full_of_hot_air::run,
+];
+
+// Seeded generators of random inputs, which are always valid puzzle inputs for their day.
+const GENERATORS: [GenFn; 25] = [
+    day_01_calorie_counting::generate_input,
+    day_02_rock_paper_scissors::generate_input,
+    day_03_rucksack_reorganization::generate_input,
+    day_04_camp_cleanup::generate_input,
+    day_05_supply_stacks::generate_input,
+    day_06_tuning_trouble::generate_input,
+    day_07_no_space_left_on_device::generate_input,
+    day_08_treetop_tree_house::generate_input,
+    day_09_rope_bridge::generate_input,
+    day_10_cathode_ray_tube::generate_input,
+    day_11_monkey_in_the_middle::generate_input,
+    day_12_hill_climbing_algorithm::generate_input,
+    day_13_distress_signal::generate_input,
+    day_14_regolith_reservoir::generate_input,
+    day_15_beacon_exclusion_zone::generate_input,
+    day_16_proboscidea_volcanium::generate_input,
+    day_17_pyroclastic_flow::generate_input,
+    day_18_boiling_boulder::generate_input,
+    day_19_not_enough_minerals::generate_input,
+    day_20_grove_positioning_system::generate_input,
+    day_21_monkey_math::generate_input,
+    day_22_monkey_map::generate_input,
+    day_23_unstable_diffusion::generate_input,
+    day_24_blizzard_basin::generate_input,
+    day_25_full_of_hot_air::generate_input,
+];
 
 fn main() -> ExitCode {
-    let args: Vec<_> = env::args().collect();
-    let days = [
-        day_01_calorie_counting::run,
-        day_02_rock_paper_scissors::run,
-        day_03_rucksack_reorganization::run,
-        day_04_camp_cleanup::run,
-        day_05_supply_stacks::run,
-        day_06_tuning_trouble::run,
-        day_07_no_space_left_on_device::run,
-        day_08_treetop_tree_house::run,
-        day_09_rope_bridge::run,
-        day_10_cathode_ray_tube::run,
-        day_11_monkey_in_the_middle::run,
-        day_12_hill_climbing_algorithm::run,
-        day_13_distress_signal::run,
-        day_14_regolith_reservoir::run,
-        day_15_beacon_exclusion_zone::run,
-        day_16_proboscidea_volcanium::run,
-        day_17_pyroclastic_flow::run,
-        day_18_boiling_boulder::run,
-        day_19_not_enough_minerals::run,
-        day_20_grove_positioning_system::run,
-        day_21_monkey_math::run,
-        day_22_monkey_map::run,
-        day_23_unstable_diffusion::run,
-        day_24_blizzard_basin::run,
-        day_25_full_of_hot_air::run,
+    let mut args: Vec<_> = env::args().collect();
+    // Alternative entry points for the days whose solutions are searches; they report how many
+    // states were explored with different search strategies.
+    let search_stats_fns: [(usize, DayFn); 4] = [
+        (12, day_12_hill_climbing_algorithm::search_stats),
+        (16, day_16_proboscidea_volcanium::search_stats),
+        (19, day_19_not_enough_minerals::search_stats),
+        (24, day_24_blizzard_basin::search_stats),
+    ];
+
+    // Alternative entry points for the days that can show how their state changes step by step.
+    let trace_fns: [(usize, DayFn); 3] = [
+        (5, day_05_supply_stacks::trace),
+        (12, day_12_hill_climbing_algorithm::trace),
+        (24, day_24_blizzard_basin::trace),
+    ];
+
+    // Alternative entry points for the days that can animate how their solution works on the
+    // terminal.
+    let animate_fns: [(usize, DayFn); 1] = [(12, day_12_hill_climbing_algorithm::animate)];
+
+    // Alternative entry points for the days that only need to look at a line or a few bytes at a
+    // time; they read the input as they go, so it doesn't need to fit in memory.
+    let streaming_fns: [(usize, StreamingDayFn); 8] = [
+        (1, day_01_calorie_counting::run_streaming),
+        (2, day_02_rock_paper_scissors::run_streaming),
+        (3, day_03_rucksack_reorganization::run_streaming),
+        (4, day_04_camp_cleanup::run_streaming),
+        (6, day_06_tuning_trouble::run_streaming),
+        (9, day_09_rope_bridge::run_streaming),
+        (10, day_10_cathode_ray_tube::run_streaming),
+        (25, day_25_full_of_hot_air::run_streaming),
+    ];
+
+    // Alternative entry points for the days that can answer other questions about their input than
+    // the puzzle's ones. The query is given with the --query flag.
+    let query_fns: [(usize, QueryFn); 7] = [
+        (1, day_01_calorie_counting::query),
+        (2, day_02_rock_paper_scissors::query),
+        (3, day_03_rucksack_reorganization::query),
+        (4, day_04_camp_cleanup::query),
+        (5, day_05_supply_stacks::query),
+        (6, day_06_tuning_trouble::query),
+        (7, day_07_no_space_left_on_device_with_tree::query),
     ];
 
-    let run_single_day = |day_num: usize| {
+    // Queries that can also read their input as they go, with --stream and --query together.
+    let streaming_query_fns: [(usize, StreamingQueryFn); 1] =
+        [(6, day_06_tuning_trouble::query_streaming)];
+
+    let run_single_day = |day_num: usize, day_fn: &dyn Fn(&str) -> Result<String, String>| {
         let instant = time::Instant::now();
         let filename = format!("inputs/{day_num:02}.txt");
-        match fs::read_to_string(&filename) {
-            Ok(input) => {
-                let output = days[day_num - 1](&input);
-                let time_annotation = format_time_annotation(instant.elapsed());
-                Ok(format!("Day {day_num}{time_annotation}: {output}"))
-            }
-            Err(err) => Err(format!("Error reading {filename}: {err}")),
-        }
+        let input = fs::read_to_string(&filename)
+            .map_err(|err| format!("Error reading {filename}: {err}"))?;
+        let output = day_fn(&input).map_err(|err| format!("Day {day_num}: {err}"))?;
+        let time_annotation = format_time_annotation(instant.elapsed());
+        Ok(format!("Day {day_num}{time_annotation}: {output}"))
+    };
+    let run_streaming_day = |day_num: usize,
+                             day_fn: &dyn Fn(&mut dyn BufRead) -> Result<String, String>|
+     -> Result<String, String> {
+        let instant = time::Instant::now();
+        let filename = format!("inputs/{day_num:02}.txt");
+        let file =
+            fs::File::open(&filename).map_err(|err| format!("Error reading {filename}: {err}"))?;
+        let output =
+            day_fn(&mut BufReader::new(file)).map_err(|err| format!("Day {day_num}: {err}"))?;
+        let time_annotation = format_time_annotation(instant.elapsed());
+        Ok(format!("Day {day_num}{time_annotation}: {output}"))
     };
     let print_day_result = |result: &_| match result {
         Ok(output) => println!("{output}"),
         Err(err_output) => eprintln!("{err_output}"),
     };
 
+    if args.get(1).is_some_and(|arg| arg == "gen") {
+        return generate_input(&args);
+    }
+    if args.get(1).is_some_and(|arg| arg == "fuzz") {
+        return run_fuzzer(&args);
+    }
+
+    // Unlike the other flags, --query has a value, so it's taken out of the args first.
+    let query = match args.iter().position(|arg| arg == "--query") {
+        Some(i) if i + 1 < args.len() => {
+            let query = args.remove(i + 1);
+            args.remove(i);
+            Some(query)
+        }
+        Some(_) => return usage(&args[0]),
+        None => None,
+    };
+
+    let (flags, args): (Vec<_>, Vec<_>) = args.iter().partition(|arg| arg.starts_with("--"));
+    let (show_search_stats, stream_input, trace_steps, animate) = match &flags[..] {
+        [] => (false, false, false, false),
+        [flag] if *flag == "--stats" && query.is_none() => (true, false, false, false),
+        [flag] if *flag == "--stream" => (false, true, false, false),
+        [flag] if *flag == "--trace" && query.is_none() => (false, false, true, false),
+        [flag] if *flag == "--animate" && query.is_none() => (false, false, false, true),
+        _ => return usage(args[0]),
+    };
+
     match args.len() {
-        1 => {
-            let results: Vec<_> = (1..=days.len())
+        1 if flags.is_empty() && query.is_none() => {
+            let results: Vec<_> = (1..=DAYS.len())
                 .into_par_iter()
-                .map(run_single_day)
+                .map(|day_num| run_single_day(day_num, &|input| Ok(DAYS[day_num - 1](input))))
                 .collect();
 
             for result in results {
@@ -96,26 +231,171 @@ fn main() -> ExitCode {
                 eprintln!("Invalid day number");
                 return ExitCode::FAILURE;
             };
-            if day_num < 1 || day_num > days.len() {
+            if day_num < 1 || day_num > DAYS.len() {
                 eprintln!("Day number out of range");
                 return ExitCode::FAILURE;
             }
 
-            let result = run_single_day(day_num);
+            if stream_input {
+                let result = if let Some(query) = &query {
+                    let Some(&(_, query_fn)) =
+                        streaming_query_fns.iter().find(|(d, _)| *d == day_num)
+                    else {
+                        eprintln!("Day {day_num} has no streaming queries");
+                        return ExitCode::FAILURE;
+                    };
+                    run_streaming_day(day_num, &|input| query_fn(input, query))
+                } else {
+                    let Some(&(_, streaming_fn)) =
+                        streaming_fns.iter().find(|(d, _)| *d == day_num)
+                    else {
+                        eprintln!("Day {day_num} has no streaming mode");
+                        return ExitCode::FAILURE;
+                    };
+                    run_streaming_day(day_num, &|input| Ok(streaming_fn(input)))
+                };
+                print_day_result(&result);
+                if result.is_err() {
+                    return ExitCode::FAILURE;
+                };
+                return ExitCode::SUCCESS;
+            }
+
+            if let Some(query) = query {
+                let Some(&(_, query_fn)) = query_fns.iter().find(|(d, _)| *d == day_num) else {
+                    eprintln!("Day {day_num} has no queries");
+                    return ExitCode::FAILURE;
+                };
+                let result = run_single_day(day_num, &|input| query_fn(input, &query));
+                print_day_result(&result);
+                if result.is_err() {
+                    return ExitCode::FAILURE;
+                };
+                return ExitCode::SUCCESS;
+            }
+
+            let day_fn = if show_search_stats {
+                let Some(&(_, stats_fn)) = search_stats_fns.iter().find(|(d, _)| *d == day_num)
+                else {
+                    eprintln!("Day {day_num} has no search stats");
+                    return ExitCode::FAILURE;
+                };
+                stats_fn
+            } else if trace_steps {
+                let Some(&(_, trace_fn)) = trace_fns.iter().find(|(d, _)| *d == day_num) else {
+                    eprintln!("Day {day_num} has no trace mode");
+                    return ExitCode::FAILURE;
+                };
+                trace_fn
+            } else if animate {
+                let Some(&(_, animate_fn)) = animate_fns.iter().find(|(d, _)| *d == day_num) else {
+                    eprintln!("Day {day_num} has no animation");
+                    return ExitCode::FAILURE;
+                };
+                animate_fn
+            } else {
+                DAYS[day_num - 1]
+            };
+
+            let result = run_single_day(day_num, &|input| Ok(day_fn(input)));
             print_day_result(&result);
             if result.is_err() {
                 return ExitCode::FAILURE;
             };
         }
-        _ => {
-            eprintln!("Usage: {} [day_number]", args[0]);
-            return ExitCode::FAILURE;
+        _ => return usage(args[0]),
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn usage(program: &str) -> ExitCode {
+    eprintln!(
+        "Usage: {program} [day_number] [--stats | --stream | --trace | --animate] [--query QUERY]"
+    );
+    eprintln!("       {program} gen <day_number> [--seed S] [--size N]");
+    eprintln!("       {program} fuzz <day_number> [--seed S] [--iterations N]");
+    ExitCode::FAILURE
+}
+
+// Prints a random input for a day, e.g. `gen 15 --seed 7 --size 100`. The same seed and size
+// always generate the same input.
+fn generate_input(args: &[String]) -> ExitCode {
+    let usage = || {
+        eprintln!("Usage: {} gen <day_number> [--seed S] [--size N]", args[0]);
+        ExitCode::FAILURE
+    };
+    let Some(Ok(day_num)) = args.get(2).map(|arg| arg.parse::<usize>()) else {
+        return usage();
+    };
+    if day_num < 1 || day_num > GENERATORS.len() {
+        eprintln!("Day number out of range");
+        return ExitCode::FAILURE;
+    }
+
+    let (mut seed, mut size) = (0, 10);
+    for option in args[3..].chunks(2) {
+        let value = option.get(1).and_then(|value| value.parse().ok());
+        match (option[0].as_str(), value) {
+            ("--seed", Some(value)) => seed = value,
+            ("--size", Some(value)) => size = value as usize,
+            _ => return usage(),
         }
     }
 
+    // Not println, as some inputs (like day 17's) must not end with a newline.
+    print!("{}", GENERATORS[day_num - 1](&mut Rng::new(seed), size));
     ExitCode::SUCCESS
 }
 
+// Fuzzes the input parsers of a day, e.g. `fuzz 13 --iterations 1000000`, saving the inputs that
+// make it panic on the fuzz directory.
+fn run_fuzzer(args: &[String]) -> ExitCode {
+    let usage = || {
+        eprintln!(
+            "Usage: {} fuzz <day_number> [--seed S] [--iterations N]",
+            args[0]
+        );
+        ExitCode::FAILURE
+    };
+    let Some(Ok(day_num)) = args.get(2).map(|arg| arg.parse::<usize>()) else {
+        return usage();
+    };
+    let targets: Vec<_> = fuzz::TARGETS.iter().filter(|t| t.day == day_num).collect();
+    if targets.is_empty() {
+        let mut days: Vec<_> = fuzz::TARGETS.iter().map(|t| t.day).collect();
+        days.dedup();
+        let days: Vec<_> = days.iter().map(|day| day.to_string()).collect();
+        eprintln!(
+            "Day {day_num} has no fuzz target, try one of {}",
+            days.join(", ")
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let (mut seed, mut iterations) = (0, 100_000);
+    for option in args[3..].chunks(2) {
+        let value = option.get(1).and_then(|value| value.parse().ok());
+        match (option[0].as_str(), value) {
+            ("--seed", Some(value)) => seed = value,
+            ("--iterations", Some(value)) => iterations = value as usize,
+            _ => return usage(),
+        }
+    }
+
+    let mut total_findings = 0;
+    for target in targets {
+        let findings = fuzz::fuzz(target, seed, iterations);
+        println!("{}: {findings} panic locations found", target.name);
+        total_findings += findings;
+    }
+    if total_findings > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
 fn format_time_annotation(elapsed: time::Duration) -> String {
     if elapsed.as_millis() < 1 {
         "".to_string()
@@ -123,3 +403,18 @@ fn format_time_annotation(elapsed: time::Duration) -> String {
         format!(" ({elapsed:.0?})")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The generated inputs must be solvable; the days panic on invalid inputs.
+    #[test]
+    fn generated_inputs_are_valid() {
+        for (day_fn, generator) in DAYS.iter().zip(GENERATORS) {
+            for seed in 0..3 {
+                day_fn(&generator(&mut Rng::new(seed), 8));
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+// A minimal take on property-based testing, so that invariants can be checked against lots of
+// random values without bringing in an external crate. When a property fails, the failing value is
+// shrunk into a simpler one that still fails, which is usually much easier to make sense of.
+use crate::rng::Rng;
+use std::{
+    env,
+    fmt::Debug,
+    ops::RangeInclusive,
+    panic::{self, AssertUnwindSafe},
+};
+
+// Knows how to generate random values of some type, and how to simplify them.
+pub trait Strategy {
+    type Value: Clone + Debug;
+
+    fn generate(&self, rng: &mut Rng) -> Self::Value;
+
+    // Values that are simpler than the given one, the simplest ones first. Shrinking stops when
+    // none of them fail the property.
+    fn shrink(&self, _value: &Self::Value) -> Vec<Self::Value> {
+        vec![]
+    }
+}
+
+const CASES: usize = 256;
+const MAX_SHRINK_STEPS: usize = 1000;
+
+// Checks a property on random values, panicking with a shrunk counterexample if it doesn't hold.
+// Panics inside the property count as failures too. The values depend on a fixed seed, so runs are
+// reproducible, but the PROPERTY_SEED env var can be set to try different ones.
+pub fn check<S: Strategy>(strategy: &S, property: impl Fn(&S::Value) -> bool) {
+    let seed = env::var("PROPERTY_SEED").map_or(0, |s| s.parse().expect("seed should be a number"));
+    let mut rng = Rng::new(seed);
+    let holds = |value: &S::Value| {
+        panic::catch_unwind(AssertUnwindSafe(|| property(value))).unwrap_or(false)
+    };
+
+    for case in 0..CASES {
+        let value = strategy.generate(&mut rng);
+        if holds(&value) {
+            continue;
+        }
+
+        let mut shrunk = value.clone();
+        let mut steps = 0;
+        while steps < MAX_SHRINK_STEPS {
+            let Some(simpler) = strategy.shrink(&shrunk).into_iter().find(|v| !holds(v)) else {
+                break;
+            };
+            shrunk = simpler;
+            steps += 1;
+        }
+        panic!(
+            "property failed on case {case} with seed {seed}\n\
+            original value: {value:?}\n\
+            shrunk value ({steps} steps): {shrunk:?}"
+        );
+    }
+}
+
+// Integers in a range, which shrink towards the one closest to 0.
+pub struct Ints(RangeInclusive<i64>);
+
+pub fn ints(range: RangeInclusive<i64>) -> Ints {
+    Ints(range)
+}
+
+impl Strategy for Ints {
+    type Value = i64;
+
+    fn generate(&self, rng: &mut Rng) -> i64 {
+        // Edge values find off-by-one errors much more often than values picked uniformly.
+        let (start, end) = (*self.0.start(), *self.0.end());
+        match rng.below(10) {
+            0 => start,
+            1 => end,
+            _ => rng.range(self.0.clone()),
+        }
+    }
+
+    fn shrink(&self, &value: &i64) -> Vec<i64> {
+        let target = 0.clamp(*self.0.start(), *self.0.end());
+        let mut candidates = vec![
+            target,
+            value - (value - target) / 2,
+            value - (value - target).signum(),
+        ];
+        candidates.dedup();
+        candidates.retain(|&v| v != value);
+        candidates
+    }
+}
+
+// Vectors with lengths in a range, which shrink by removing elements and then by shrinking them.
+pub struct Vecs<S> {
+    element: S,
+    len: RangeInclusive<usize>,
+}
+
+pub fn vecs<S: Strategy>(element: S, len: RangeInclusive<usize>) -> Vecs<S> {
+    Vecs { element, len }
+}
+
+impl<S: Strategy> Strategy for Vecs<S> {
+    type Value = Vec<S::Value>;
+
+    fn generate(&self, rng: &mut Rng) -> Self::Value {
+        let len = rng.range(*self.len.start() as i64..=*self.len.end() as i64) as usize;
+        (0..len).map(|_| self.element.generate(rng)).collect()
+    }
+
+    fn shrink(&self, value: &Self::Value) -> Vec<Self::Value> {
+        let min_len = *self.len.start();
+        let mut candidates = vec![];
+        if value.len() > min_len {
+            candidates.push(value[..min_len].to_vec());
+            let half_len = min_len.max(value.len() / 2);
+            if half_len > min_len {
+                candidates.push(value[..half_len].to_vec());
+                candidates.push(value[value.len() - half_len..].to_vec());
+            }
+            for i in 0..value.len() {
+                let mut removed = value.clone();
+                removed.remove(i);
+                candidates.push(removed);
+            }
+        }
+        for (i, element) in value.iter().enumerate() {
+            for simpler in self.element.shrink(element) {
+                let mut shrunk = value.clone();
+                shrunk[i] = simpler;
+                candidates.push(shrunk);
+            }
+        }
+        candidates
+    }
+}
+
+// Strings made of the characters of an alphabet, which shrink by removing characters and then by
+// replacing them with the alphabet's first one.
+pub struct Strings {
+    alphabet: Vec<char>,
+    len: RangeInclusive<usize>,
+}
+
+pub fn strings(alphabet: &str, len: RangeInclusive<usize>) -> Strings {
+    assert!(!alphabet.is_empty(), "alphabet should not be empty");
+    Strings {
+        alphabet: alphabet.chars().collect(),
+        len,
+    }
+}
+
+impl Strategy for Strings {
+    type Value = String;
+
+    fn generate(&self, rng: &mut Rng) -> String {
+        let len = rng.range(*self.len.start() as i64..=*self.len.end() as i64) as usize;
+        (0..len).map(|_| *rng.choose(&self.alphabet)).collect()
+    }
+
+    fn shrink(&self, value: &String) -> Vec<String> {
+        let chars: Vec<char> = value.chars().collect();
+        let mut candidates = vec![];
+        if chars.len() > *self.len.start() {
+            for i in 0..chars.len() {
+                let mut removed = chars.clone();
+                removed.remove(i);
+                candidates.push(removed.into_iter().collect());
+            }
+        }
+        let simplest_char = self.alphabet[0];
+        for i in 0..chars.len() {
+            if chars[i] != simplest_char {
+                let mut replaced = chars.clone();
+                replaced[i] = simplest_char;
+                candidates.push(replaced.into_iter().collect());
+            }
+        }
+        candidates
+    }
+}
+
+// Tuples of strategies generate tuples of values, and shrink one element at a time.
+impl<A: Strategy, B: Strategy> Strategy for (A, B) {
+    type Value = (A::Value, B::Value);
+
+    fn generate(&self, rng: &mut Rng) -> Self::Value {
+        (self.0.generate(rng), self.1.generate(rng))
+    }
+
+    fn shrink(&self, (a, b): &Self::Value) -> Vec<Self::Value> {
+        let shrunk_a = self.0.shrink(a).into_iter().map(|a| (a, b.clone()));
+        let shrunk_b = self.1.shrink(b).into_iter().map(|b| (a.clone(), b));
+        shrunk_a.chain(shrunk_b).collect()
+    }
+}
+
+impl<A: Strategy, B: Strategy, C: Strategy> Strategy for (A, B, C) {
+    type Value = (A::Value, B::Value, C::Value);
+
+    fn generate(&self, rng: &mut Rng) -> Self::Value {
+        (
+            self.0.generate(rng),
+            self.1.generate(rng),
+            self.2.generate(rng),
+        )
+    }
+
+    fn shrink(&self, (a, b, c): &Self::Value) -> Vec<Self::Value> {
+        let shrunk_a = self
+            .0
+            .shrink(a)
+            .into_iter()
+            .map(|a| (a, b.clone(), c.clone()));
+        let shrunk_b = self
+            .1
+            .shrink(b)
+            .into_iter()
+            .map(|b| (a.clone(), b, c.clone()));
+        let shrunk_c = self
+            .2
+            .shrink(c)
+            .into_iter()
+            .map(|c| (a.clone(), b.clone(), c));
+        shrunk_a.chain(shrunk_b).chain(shrunk_c).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrinks_to_minimal_counterexample() {
+        let result = panic::catch_unwind(|| {
+            check(&vecs(ints(-1000..=1000), 0..=20), |v| {
+                v.iter().all(|&n| n < 50)
+            })
+        });
+        let message = *result
+            .expect_err("property should fail")
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.ends_with("): [50]"), "not shrunk enough: {message}");
+    }
+}
@@ -0,0 +1,54 @@
+// Not all of the methods are used by the input generators, but they make for a complete API.
+#![allow(dead_code)]
+
+use std::ops::RangeInclusive;
+
+// A small xorshift64* pseudo-random number generator, so that generating random inputs doesn't
+// require an external crate. Not suitable for anything security-related, obviously.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Scramble the seed with a SplitMix64 step so that similar seeds give unrelated sequences,
+        // and so that a seed of 0, which xorshift can't get out of, is fine too.
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        Rng(if z == 0 { 1 } else { z })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // A random number in 0..n. The modulo bias is negligible for the small ranges used here.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "range should not be empty");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn range(&mut self, range: RangeInclusive<i64>) -> i64 {
+        let (start, end) = range.into_inner();
+        assert!(start <= end, "range should not be empty");
+        let len = end.abs_diff(start) + 1;
+        start.wrapping_add((self.next_u64() % len) as i64)
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
@@ -21,6 +21,8 @@ mod tests {
     #[test] fn day_14_regolith_reservoir_sample() { run_sample_test(day_14_regolith_reservoir::run, "./samples/14.txt") }
     #[test] fn day_15_beacon_exclusion_zone_sample() { run_sample_test(day_15_beacon_exclusion_zone::run, "./samples/15.txt") }
     #[test] fn day_16_proboscidea_volcanium_sample() { run_sample_test(day_16_proboscidea_volcanium::run, "./samples/16.txt") }
+    #[test] fn day_16_proboscidea_volcanium_many_valves() { run_sample_test(day_16_proboscidea_volcanium::run, "./samples/16_many_valves.txt") }
+    #[test] fn day_16_proboscidea_volcanium_unreachable_valves() { run_sample_test(day_16_proboscidea_volcanium::run, "./samples/16_unreachable_valves.txt") }
     #[test] fn day_17_pyroclastic_flow_sample() { run_sample_test(day_17_pyroclastic_flow::run, "./samples/17.txt") }
     #[test] fn day_18_boiling_boulder_sample() { run_sample_test(day_18_boiling_boulder::run, "./samples/18.txt") }
     #[test] fn day_19_not_enough_minerals_sample() { run_sample_test(day_19_not_enough_minerals::run, "./samples/19.txt") }
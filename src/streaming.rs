@@ -0,0 +1,14 @@
+use std::io::{BufRead, Read};
+
+// The lines of an input that is read as it goes instead of being loaded into memory all at once.
+pub fn lines(input: &mut dyn BufRead) -> impl Iterator<Item = String> + '_ {
+    input
+        .lines()
+        .map(|line| line.expect("input should be readable"))
+}
+
+// Same as `lines`, but for inputs that are a single sequence of bytes.
+pub fn bytes(input: &mut dyn BufRead) -> impl Iterator<Item = u8> + '_ {
+    // Reads from the BufRead's buffer, so this doesn't do a syscall for every byte.
+    Read::bytes(input).map(|byte| byte.expect("input should be readable"))
+}